@@ -0,0 +1,231 @@
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+use crate::rigid_body::RigidBody;
+use serde::{Deserialize, Serialize};
+
+// Refers to a body for as long as it exists. Removing other bodies doesn't change it, and once the
+// body itself is removed the handle stops working, even if a new body ends up in the same slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct BodyHandle {
+    index: usize,
+    generation: u32,
+}
+impl BodyHandle {
+    // Slot of the body in its BodySet, this stays the same while the body exists
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    // How many bodies were in the slot before this one
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+impl fmt::Display for BodyHandle {
+    // Numbered from 1, the generation is only shown once a slot has been reused
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.generation == 0 {
+            write!(f, "{}", self.index + 1)
+        } else {
+            write!(f, "{}v{}", self.index + 1, self.generation)
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    body: Option<Box<dyn RigidBody>>,
+}
+
+// Stores the bodies in slots that never move, so indices stay valid while bodies get removed
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct BodySet {
+    slots: Vec<Slot>,
+    free_slots: Vec<usize>,
+}
+impl BodySet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, body: Box<dyn RigidBody>) -> BodyHandle {
+        if let Some(index) = self.free_slots.pop() {
+            let slot = &mut self.slots[index];
+            slot.body = Some(body);
+            return BodyHandle {
+                index,
+                generation: slot.generation,
+            };
+        }
+        self.slots.push(Slot {
+            generation: 0,
+            body: Some(body),
+        });
+        BodyHandle {
+            index: self.slots.len() - 1,
+            generation: 0,
+        }
+    }
+
+    // Returns None if the body was already removed
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<Box<dyn RigidBody>> {
+        let slot = self.slots.get_mut(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let body = slot.body.take()?;
+        // Old handles to this slot stop working
+        slot.generation += 1;
+        self.free_slots.push(handle.index);
+        Some(body)
+    }
+
+    pub fn get(&self, handle: BodyHandle) -> Option<&dyn RigidBody> {
+        let slot = self.slots.get(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.body.as_deref()
+    }
+
+    pub fn get_mut(&mut self, handle: BodyHandle) -> Option<&mut Box<dyn RigidBody>> {
+        let slot = self.slots.get_mut(handle.index)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.body.as_mut()
+    }
+
+    // Body in the slot at `index`, if there is one
+    pub fn get_at(&self, index: usize) -> Option<&dyn RigidBody> {
+        self.slots.get(index)?.body.as_deref()
+    }
+    pub fn get_at_mut(&mut self, index: usize) -> Option<&mut Box<dyn RigidBody>> {
+        self.slots.get_mut(index)?.body.as_mut()
+    }
+
+    // Handle of the body in the slot at `index`, if there is one
+    pub fn get_handle(&self, index: usize) -> Option<BodyHandle> {
+        let slot = self.slots.get(index)?;
+        slot.body.as_ref().map(|_| BodyHandle {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    // Number of slots, including empty ones. Every index below this can be passed to get_at
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Box<dyn RigidBody>> {
+        self.slots.iter().filter_map(|slot| slot.body.as_ref())
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Box<dyn RigidBody>> {
+        self.slots.iter_mut().filter_map(|slot| slot.body.as_mut())
+    }
+    pub fn iter_with_handles(&self) -> impl Iterator<Item = (BodyHandle, &Box<dyn RigidBody>)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = BodyHandle {
+                index,
+                generation: slot.generation,
+            };
+            slot.body.as_ref().map(|body| (handle, body))
+        })
+    }
+    pub fn iter_mut_with_handles(
+        &mut self,
+    ) -> impl Iterator<Item = (BodyHandle, &mut Box<dyn RigidBody>)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let handle = BodyHandle {
+                    index,
+                    generation: slot.generation,
+                };
+                slot.body.as_mut().map(|body| (handle, body))
+            })
+    }
+}
+// Panics if the slot is empty, the same way a Vec panics for an index that is out of bounds
+impl Index<usize> for BodySet {
+    type Output = Box<dyn RigidBody>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.slots[index]
+            .body
+            .as_ref()
+            .expect("no body in this slot")
+    }
+}
+impl IndexMut<usize> for BodySet {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.get_at_mut(index).expect("no body in this slot")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rigid_circle::RigidCircle;
+    use macroquad::prelude::*;
+
+    fn get_ball(x: f32) -> Box<dyn RigidBody> {
+        Box::new(RigidCircle::new(1., vec2(x, 0.), 1.))
+    }
+
+    #[test]
+    fn removing_a_body_doesnt_change_the_other_handles() {
+        let mut bodies = BodySet::new();
+        let a = bodies.insert(get_ball(1.));
+        let b = bodies.insert(get_ball(2.));
+        let c = bodies.insert(get_ball(3.));
+        bodies.remove(b).unwrap();
+        assert_eq!(bodies.get(a).unwrap().get_pos().x, 1.);
+        assert_eq!(bodies.get(c).unwrap().get_pos().x, 3.);
+        assert!(bodies.get(b).is_none());
+        assert_eq!(bodies.iter().count(), 2);
+        assert_eq!(bodies.slot_count(), 3);
+    }
+
+    #[test]
+    fn stale_handles_dont_find_the_body_that_reused_their_slot() {
+        let mut bodies = BodySet::new();
+        let old = bodies.insert(get_ball(1.));
+        bodies.remove(old).unwrap();
+        let new = bodies.insert(get_ball(2.));
+        assert_eq!(new.index(), old.index());
+        assert_eq!(new.generation(), old.generation() + 1);
+        assert!(bodies.get(old).is_none());
+        assert!(bodies.get_mut(old).is_none());
+        assert!(bodies.remove(old).is_none());
+        assert_eq!(bodies.get(new).unwrap().get_pos().x, 2.);
+        assert_eq!(bodies.get_handle(new.index()), Some(new));
+    }
+
+    #[test]
+    fn removing_twice_frees_the_slot_once() {
+        let mut bodies = BodySet::new();
+        let handle = bodies.insert(get_ball(1.));
+        assert!(bodies.remove(handle).is_some());
+        assert!(bodies.remove(handle).is_none());
+        // The slot is only handed out once
+        let a = bodies.insert(get_ball(2.));
+        let b = bodies.insert(get_ball(3.));
+        assert_ne!(a.index(), b.index());
+    }
+
+    #[test]
+    fn handles_are_numbered_from_one_with_the_generation_once_reused() {
+        let mut bodies = BodySet::new();
+        let handle = bodies.insert(get_ball(1.));
+        assert_eq!(handle.to_string(), "1");
+        bodies.remove(handle);
+        assert_eq!(bodies.insert(get_ball(1.)).to_string(), "1v1");
+    }
+}
@@ -0,0 +1,124 @@
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+
+// Each notch of the mouse wheel zooms in or out by this factor
+const ZOOM_PER_SCROLL: f32 = 1.1;
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 20.;
+
+// Decides which part of the world is shown on the screen. At zoom 1 it shows the whole world.
+// World coordinates have y going up, screen coordinates have y going down.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    // World position in the middle of the screen
+    center: Vec2,
+    zoom: f32,
+    world_size: Vec2,
+    pub follow_selected: bool,
+    // Mouse position on the screen and the centre when panning started
+    pan_start: Option<(Vec2, Vec2)>,
+}
+impl Camera {
+    pub fn new(world_size: Vec2) -> Self {
+        Self {
+            center: world_size * 0.5,
+            zoom: 1.,
+            world_size,
+            follow_selected: false,
+            pan_start: None,
+        }
+    }
+
+    // Pixels per metre. At zoom 1 the whole world fits in the window without stretching it
+    pub fn get_scale(&self) -> f32 {
+        let fit = get_screen_size() / self.world_size;
+        fit.min_element() * self.zoom
+    }
+
+    pub fn world_to_screen(&self, pos: Vec2) -> Vec2 {
+        let screen_size = get_screen_size();
+        let offset = (pos - self.center) * self.get_scale();
+        vec2(
+            screen_size.x * 0.5 + offset.x,
+            screen_size.y * 0.5 - offset.y,
+        )
+    }
+
+    pub fn screen_to_world(&self, pos: Vec2) -> Vec2 {
+        let screen_size = get_screen_size();
+        let offset = vec2(pos.x - screen_size.x * 0.5, screen_size.y * 0.5 - pos.y);
+        self.center + offset / self.get_scale()
+    }
+
+    // Length in metres to length in pixels
+    pub fn metres_to_pixels(&self, length: f32) -> f32 {
+        length * self.get_scale()
+    }
+
+    // The part of the world on the screen, as (min, max)
+    pub fn get_visible_area(&self) -> (Vec2, Vec2) {
+        let screen_size = get_screen_size();
+        let bottom_left = self.screen_to_world(vec2(0., screen_size.y));
+        let top_right = self.screen_to_world(vec2(screen_size.x, 0.));
+        (bottom_left, top_right)
+    }
+
+    pub fn get_mouse_pos(&self) -> Vec2 {
+        self.screen_to_world(mouse_position().into())
+    }
+
+    // Zooms with the mouse wheel around the mouse, so the point under it stays where it is
+    pub fn update_zoom(&mut self) {
+        let scroll = mouse_wheel().1;
+        if scroll == 0. {
+            return;
+        }
+        let mouse_pos = mouse_position().into();
+        let before = self.screen_to_world(mouse_pos);
+        self.zoom = (self.zoom * ZOOM_PER_SCROLL.powf(scroll.signum())).clamp(MIN_ZOOM, MAX_ZOOM);
+        if !self.follow_selected {
+            self.center += before - self.screen_to_world(mouse_pos);
+        }
+    }
+
+    pub fn start_pan(&mut self) {
+        self.pan_start = Some((mouse_position().into(), self.center));
+        self.follow_selected = false;
+    }
+
+    // Moves the world along with the mouse until `stop_pan`
+    pub fn update_pan(&mut self) {
+        let Some((mouse_start, center_start)) = self.pan_start else {
+            return;
+        };
+        let moved = Vec2::from(mouse_position()) - mouse_start;
+        self.center = center_start + vec2(-moved.x, moved.y) / self.get_scale();
+    }
+
+    pub fn stop_pan(&mut self) {
+        self.pan_start = None;
+    }
+
+    pub fn look_at(&mut self, pos: Vec2) {
+        self.center = pos;
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Show camera", |ui| {
+            ui.label("Scroll to zoom, drag with the middle mouse button to pan");
+            ui.horizontal(|ui| {
+                ui.label("Zoom:");
+                ui.add(egui::Slider::new(&mut self.zoom, MIN_ZOOM..=MAX_ZOOM).logarithmic(true));
+            });
+            ui.checkbox(&mut self.follow_selected, "follow selected body");
+            if ui.button("Reset view").clicked() {
+                *self = Camera::new(self.world_size);
+            }
+        });
+    }
+}
+
+// Size of the window in pixels, it can change every frame
+fn get_screen_size() -> Vec2 {
+    vec2(screen_width(), screen_height())
+}
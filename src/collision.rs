@@ -0,0 +1,720 @@
+use egui_macroquad::egui::Ui;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Number of collision layers shown in the ui, the filter itself has room for 32
+const UI_LAYERS: u32 = 8;
+// Binary search steps used to find the time of impact of polygons
+const SWEEP_REFINEMENTS: usize = 12;
+// Polygons sweeping further than this many steps get stopped at the last one
+const MAX_SWEEP_STEPS: usize = 1000;
+// Vertices this close to the furthest one along a direction count as being just as far
+const SUPPORT_TOLERANCE: f32 = 0.01;
+
+// Which bodies collide with each other. Every body is in the layers of its category and
+// collides with the layers in its mask, both bodies have to agree for them to collide
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionFilter {
+    pub category: u32,
+    pub mask: u32,
+}
+impl Default for CollisionFilter {
+    // In the first layer, colliding with everything
+    fn default() -> Self {
+        Self {
+            category: 1,
+            mask: u32::MAX,
+        }
+    }
+}
+impl CollisionFilter {
+    pub fn can_collide(&self, other: &CollisionFilter) -> bool {
+        self.category & other.mask != 0 && other.category & self.mask != 0
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Collision filter", |ui| {
+            ui.label("In layers:");
+            layer_checkboxes(ui, &mut self.category);
+            ui.label("Collides with layers:");
+            layer_checkboxes(ui, &mut self.mask);
+        });
+    }
+}
+
+fn layer_checkboxes(ui: &mut Ui, bits: &mut u32) {
+    ui.horizontal(|ui| {
+        for layer in 0..UI_LAYERS {
+            let mut enabled = *bits & (1 << layer) != 0;
+            if ui
+                .checkbox(&mut enabled, format!("{}", layer + 1))
+                .changed()
+            {
+                *bits ^= 1 << layer;
+            }
+        }
+    });
+}
+
+// The part of a body that takes part in collisions, in world coordinates
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Circle { center: Vec2, radius: f32 },
+    Rect { min: Vec2, max: Vec2 },
+    // Convex, with the vertices going counter clockwise
+    Polygon { vertices: Vec<Vec2> },
+}
+impl Shape {
+    // Smallest box the shape fits in, as (min, max)
+    pub fn get_bounds(&self) -> (Vec2, Vec2) {
+        match self {
+            Shape::Circle { center, radius } => (*center - *radius, *center + *radius),
+            Shape::Rect { min, max } => (*min, *max),
+            Shape::Polygon { vertices } => vertices.iter().fold(
+                (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+                |(min, max), &v| (min.min(v), max.max(v)),
+            ),
+        }
+    }
+
+    // Centre of the area, where the mass is balanced
+    pub fn get_centroid(&self) -> Vec2 {
+        match self {
+            Shape::Circle { center, .. } => *center,
+            Shape::Rect { min, max } => (*min + *max) * 0.5,
+            Shape::Polygon { vertices } => {
+                // Sum of the triangles between the first vertex and every edge
+                let origin = vertices[0];
+                let (weighted_sum, area) =
+                    get_edges(vertices).fold((Vec2::ZERO, 0.), |(sum, area), (start, end)| {
+                        let triangle_area = (start - origin).perp_dot(end - origin) * 0.5;
+                        let triangle_center = (origin + start + end) / 3.;
+                        (sum + triangle_center * triangle_area, area + triangle_area)
+                    });
+                if area > 0. {
+                    weighted_sum / area
+                } else {
+                    origin
+                }
+            }
+        }
+    }
+
+    // Point of the shape furthest along `dir`. When a whole edge is, it's the middle of the edge
+    pub fn get_support_point(&self, dir: Vec2) -> Vec2 {
+        let dir = dir.normalize_or_zero();
+        if let Shape::Circle { center, radius } = self {
+            return *center + dir * *radius;
+        }
+        let vertices = self.get_vertices().unwrap_or_default();
+        let furthest = vertices
+            .iter()
+            .map(|v| v.dot(dir))
+            .fold(f32::NEG_INFINITY, f32::max);
+        let (sum, count) = vertices
+            .iter()
+            .filter(|v| v.dot(dir) > furthest - SUPPORT_TOLERANCE)
+            .fold((Vec2::ZERO, 0.), |(sum, count), &v| (sum + v, count + 1.));
+        sum / count
+    }
+
+    pub fn translate(&self, offset: Vec2) -> Shape {
+        match self {
+            Shape::Circle { center, radius } => Shape::Circle {
+                center: *center + offset,
+                radius: *radius,
+            },
+            Shape::Rect { min, max } => Shape::Rect {
+                min: *min + offset,
+                max: *max + offset,
+            },
+            Shape::Polygon { vertices } => Shape::Polygon {
+                vertices: vertices.iter().map(|&v| v + offset).collect(),
+            },
+        }
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        match self {
+            Shape::Circle { center, radius } => center.distance(point) <= *radius,
+            Shape::Rect { min, max } => point.clamp(*min, *max) == point,
+            // Counter clockwise, so the point has to be on the left of every edge
+            Shape::Polygon { vertices } => {
+                get_edges(vertices).all(|(start, end)| (end - start).perp_dot(point - start) >= 0.)
+            }
+        }
+    }
+
+    // Corners of the shape going counter clockwise, circles don't have any
+    fn get_vertices(&self) -> Option<Vec<Vec2>> {
+        match self {
+            Shape::Circle { .. } => None,
+            Shape::Rect { min, max } => {
+                Some(vec![*min, vec2(max.x, min.y), *max, vec2(min.x, max.y)])
+            }
+            Shape::Polygon { vertices } => Some(vertices.clone()),
+        }
+    }
+
+    fn overlaps(&self, other: &Shape) -> bool {
+        match (self, other) {
+            (
+                &Shape::Circle { center, radius },
+                &Shape::Circle {
+                    center: center_1,
+                    radius: radius_1,
+                },
+            ) => center.distance(center_1) < radius + radius_1,
+            (&Shape::Circle { center, radius }, &Shape::Rect { min, max })
+            | (&Shape::Rect { min, max }, &Shape::Circle { center, radius }) => {
+                center.distance(center.clamp(min, max)) < radius
+            }
+            (
+                &Shape::Rect { min, max },
+                &Shape::Rect {
+                    min: min_1,
+                    max: max_1,
+                },
+            ) => min.x < max_1.x && max.x > min_1.x && min.y < max_1.y && max.y > min_1.y,
+            _ => get_contact(self, other).is_some(),
+        }
+    }
+}
+
+// Every edge of the polygon as (start, end), including the one from the last vertex back to the first
+fn get_edges(vertices: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(&start, &end)| (start, end))
+}
+
+// Sweeps `moving` along `displacement` and returns the fraction of the displacement after which
+// it first touches `other`, together with the normal pointing from `other` towards `moving`.
+// Returns None if they don't touch or if they already overlap at the start,
+// the latter is left to the regular collision resolution.
+pub fn get_time_of_impact(
+    moving: &Shape,
+    displacement: Vec2,
+    other: &Shape,
+) -> Option<(f32, Vec2)> {
+    if moving.overlaps(other) || displacement == Vec2::ZERO {
+        return None;
+    }
+
+    match (moving, other) {
+        (
+            &Shape::Circle { center, radius },
+            &Shape::Circle {
+                center: center_1,
+                radius: radius_1,
+            },
+        ) => {
+            let t = ray_circle(center, displacement, center_1, radius + radius_1)?;
+            let normal = (center + displacement * t - center_1).normalize_or_zero();
+            Some((t, normal))
+        }
+        (&Shape::Circle { center, radius }, &Shape::Rect { min, max }) => {
+            swept_circle_rect(center, radius, displacement, min, max)
+        }
+        (&Shape::Rect { min, max }, &Shape::Circle { center, radius }) => {
+            // Moving the box towards the circle is the same as moving the circle the other way
+            let (t, normal) = swept_circle_rect(center, radius, -displacement, min, max)?;
+            Some((t, -normal))
+        }
+        (
+            &Shape::Rect { min, max },
+            &Shape::Rect {
+                min: min_1,
+                max: max_1,
+            },
+        ) => {
+            // Shrink the moving box to a point and grow the other one by the same amount
+            let half_size = (max - min) * 0.5;
+            ray_aabb(
+                (min + max) * 0.5,
+                displacement,
+                min_1 - half_size,
+                max_1 + half_size,
+            )
+        }
+        _ => sweep_by_sampling(moving, displacement, other),
+    }
+}
+
+// There is no exact sweep for polygons, so the motion is split into steps smaller than the shapes
+// and the first step that overlaps gets narrowed down with a binary search
+fn sweep_by_sampling(moving: &Shape, displacement: Vec2, other: &Shape) -> Option<(f32, Vec2)> {
+    let smallest_size = [moving, other]
+        .iter()
+        .map(|shape| {
+            let (min, max) = shape.get_bounds();
+            (max - min).min_element()
+        })
+        .fold(f32::INFINITY, f32::min);
+    let steps = (displacement.length() / (smallest_size * 0.5).max(f32::EPSILON))
+        .ceil()
+        .clamp(1., MAX_SWEEP_STEPS as f32) as usize;
+
+    let mut before = 0.;
+    for step in 1..=steps {
+        let t = step as f32 / steps as f32;
+        if !moving.translate(displacement * t).overlaps(other) {
+            before = t;
+            continue;
+        }
+
+        let mut after = t;
+        for _ in 0..SWEEP_REFINEMENTS {
+            let middle = (before + after) * 0.5;
+            if moving.translate(displacement * middle).overlaps(other) {
+                after = middle;
+            } else {
+                before = middle;
+            }
+        }
+        // The contact normal points towards the other shape, the result points away from it
+        let contact = get_contact(&moving.translate(displacement * after), other)?;
+        return Some((before, -contact.normal));
+    }
+    None
+}
+
+// A circle sweeping past a box hits either one of the sides or one of the corners
+fn swept_circle_rect(
+    center: Vec2,
+    radius: f32,
+    displacement: Vec2,
+    min: Vec2,
+    max: Vec2,
+) -> Option<(f32, Vec2)> {
+    let (t, normal) = ray_aabb(center, displacement, min - radius, max + radius)?;
+
+    let hit = center + displacement * t;
+    let beside_side = hit.x >= min.x && hit.x <= max.x;
+    let beside_top_or_bottom = hit.y >= min.y && hit.y <= max.y;
+    if beside_side || beside_top_or_bottom {
+        return Some((t, normal));
+    }
+
+    // The hit is in one of the rounded corners of the grown box
+    let corner = vec2(
+        if hit.x < min.x { min.x } else { max.x },
+        if hit.y < min.y { min.y } else { max.y },
+    );
+    let t = ray_circle(center, displacement, corner, radius)?;
+    let normal = (center + displacement * t - corner).normalize_or_zero();
+    Some((t, normal))
+}
+
+// First point in [0, 1] where `origin + t * dir` enters the circle
+fn ray_circle(origin: Vec2, dir: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    // Solve |origin + t * dir - center|^2 = radius^2 for t
+    let offset = origin - center;
+    let a = dir.dot(dir);
+    let b = 2. * offset.dot(dir);
+    let c = offset.dot(offset) - radius * radius;
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / (2. * a);
+    (0. ..=1.).contains(&t).then_some(t)
+}
+
+// First point in [0, 1] where `origin + t * dir` enters the box, with the normal of the side it enters through
+fn ray_aabb(origin: Vec2, dir: Vec2, min: Vec2, max: Vec2) -> Option<(f32, Vec2)> {
+    let mut t_enter = f32::NEG_INFINITY;
+    let mut t_exit = f32::INFINITY;
+    let mut normal = Vec2::ZERO;
+
+    for axis in 0..2 {
+        if dir[axis] == 0. {
+            // Parallel to this pair of sides, so it has to be between them the whole way
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t_min = (min[axis] - origin[axis]) / dir[axis];
+        let t_max = (max[axis] - origin[axis]) / dir[axis];
+        let (t_near, t_far) = if t_min < t_max {
+            (t_min, t_max)
+        } else {
+            (t_max, t_min)
+        };
+
+        if t_near > t_enter {
+            t_enter = t_near;
+            normal = Vec2::ZERO;
+            normal[axis] = -dir[axis].signum();
+        }
+        t_exit = t_exit.min(t_far);
+    }
+
+    if t_enter > t_exit || !(0. ..=1.).contains(&t_enter) {
+        return None;
+    }
+    Some((t_enter, normal))
+}
+
+// Two overlapping shapes
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    // Points from the first shape towards the second one
+    pub normal: Vec2,
+    // How far the shapes have to move apart along the normal to only just touch
+    pub penetration: f32,
+}
+
+// Returns None if the shapes don't overlap
+pub fn get_contact(shape_0: &Shape, shape_1: &Shape) -> Option<Contact> {
+    match (shape_0, shape_1) {
+        (
+            &Shape::Circle { center, radius },
+            &Shape::Circle {
+                center: center_1,
+                radius: radius_1,
+            },
+        ) => {
+            let offset = center_1 - center;
+            let dist = offset.length();
+            let penetration = radius + radius_1 - dist;
+            if penetration <= 0. {
+                return None;
+            }
+            // Push straight up if the circles are exactly on top of each other
+            let normal = if dist > 0. {
+                offset / dist
+            } else {
+                vec2(0., 1.)
+            };
+            Some(Contact {
+                normal,
+                penetration,
+            })
+        }
+        (&Shape::Rect { min, max }, &Shape::Circle { center, radius }) => {
+            get_rect_circle_contact(min, max, center, radius)
+        }
+        (&Shape::Circle { center, radius }, &Shape::Rect { min, max }) => {
+            let contact = get_rect_circle_contact(min, max, center, radius)?;
+            Some(Contact {
+                normal: -contact.normal,
+                ..contact
+            })
+        }
+        (
+            &Shape::Rect { min, max },
+            &Shape::Rect {
+                min: min_1,
+                max: max_1,
+            },
+        ) => {
+            let overlap = max.min(max_1) - min.max(min_1);
+            if overlap.x <= 0. || overlap.y <= 0. {
+                return None;
+            }
+            // Separate them along the axis where they overlap the least
+            let offset = (min_1 + max_1) * 0.5 - (min + max) * 0.5;
+            if overlap.x < overlap.y {
+                Some(Contact {
+                    normal: vec2(if offset.x < 0. { -1. } else { 1. }, 0.),
+                    penetration: overlap.x,
+                })
+            } else {
+                Some(Contact {
+                    normal: vec2(0., if offset.y < 0. { -1. } else { 1. }),
+                    penetration: overlap.y,
+                })
+            }
+        }
+        (Shape::Polygon { vertices }, &Shape::Circle { center, radius }) => {
+            get_polygon_circle_contact(vertices, center, radius)
+        }
+        (&Shape::Circle { center, radius }, Shape::Polygon { vertices }) => {
+            let contact = get_polygon_circle_contact(vertices, center, radius)?;
+            Some(Contact {
+                normal: -contact.normal,
+                ..contact
+            })
+        }
+        // Everything else is made of corners, rectangles are treated as polygons
+        _ => get_polygon_contact(&shape_0.get_vertices()?, &shape_1.get_vertices()?),
+    }
+}
+
+// Separating axis test, the shapes only overlap if their projections overlap on every edge normal
+fn get_polygon_contact(vertices_0: &[Vec2], vertices_1: &[Vec2]) -> Option<Contact> {
+    let axes = get_edge_normals(vertices_0).chain(get_edge_normals(vertices_1));
+    get_smallest_overlap(axes, |axis| {
+        (project(vertices_0, axis), project(vertices_1, axis))
+    })
+}
+
+// The normal points out of the polygon towards the circle
+fn get_polygon_circle_contact(vertices: &[Vec2], center: Vec2, radius: f32) -> Option<Contact> {
+    // Besides the edges the circle can only be separated from the closest corner
+    let closest_vertex = vertices.iter().copied().min_by(|a, b| {
+        a.distance_squared(center)
+            .total_cmp(&b.distance_squared(center))
+    })?;
+    let corner_axis = (center - closest_vertex).normalize_or_zero();
+
+    let axes = get_edge_normals(vertices).chain((corner_axis != Vec2::ZERO).then_some(corner_axis));
+    get_smallest_overlap(axes, |axis| {
+        let center_along_axis = center.dot(axis);
+        (
+            project(vertices, axis),
+            (center_along_axis - radius, center_along_axis + radius),
+        )
+    })
+}
+
+// Finds the axis along which the second shape has to move the least to stop overlapping the first one.
+// `get_projections` returns the (min, max) of both shapes along an axis.
+fn get_smallest_overlap(
+    axes: impl Iterator<Item = Vec2>,
+    get_projections: impl Fn(Vec2) -> ((f32, f32), (f32, f32)),
+) -> Option<Contact> {
+    let mut smallest: Option<Contact> = None;
+    for axis in axes {
+        let ((min_0, max_0), (min_1, max_1)) = get_projections(axis);
+        // How far the second shape has to move along the axis, and how far the other way
+        let forward = max_0 - min_1;
+        let backward = max_1 - min_0;
+        if forward <= 0. || backward <= 0. {
+            return None;
+        }
+        let contact = if forward < backward {
+            Contact {
+                normal: axis,
+                penetration: forward,
+            }
+        } else {
+            Contact {
+                normal: -axis,
+                penetration: backward,
+            }
+        };
+        if smallest.is_none_or(|smallest| contact.penetration < smallest.penetration) {
+            smallest = Some(contact);
+        }
+    }
+    smallest
+}
+
+// Outward normals, the vertices have to go counter clockwise
+fn get_edge_normals(vertices: &[Vec2]) -> impl Iterator<Item = Vec2> + '_ {
+    get_edges(vertices).map(|(start, end)| -(end - start).perp().normalize_or_zero())
+}
+
+// (min, max) of the vertices along the axis
+fn project(vertices: &[Vec2], axis: Vec2) -> (f32, f32) {
+    vertices
+        .iter()
+        .map(|v| v.dot(axis))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+            (min.min(d), max.max(d))
+        })
+}
+
+// The normal points out of the rectangle towards the circle
+fn get_rect_circle_contact(min: Vec2, max: Vec2, center: Vec2, radius: f32) -> Option<Contact> {
+    let closest = center.clamp(min, max);
+
+    if closest != center {
+        let offset = center - closest;
+        let penetration = radius - offset.length();
+        if penetration <= 0. {
+            return None;
+        }
+        return Some(Contact {
+            normal: offset.normalize(),
+            penetration,
+        });
+    }
+
+    // The centre of the circle is inside, push it out the closest side
+    let sides = [
+        (vec2(-1., 0.), center.x - min.x),
+        (vec2(1., 0.), max.x - center.x),
+        (vec2(0., -1.), center.y - min.y),
+        (vec2(0., 1.), max.y - center.y),
+    ];
+    let (normal, dist) = sides
+        .into_iter()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    Some(Contact {
+        normal,
+        penetration: dist + radius,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-4, "{a} isn't {b}");
+    }
+
+    // 0.1 m thick, much thinner than the distance the bodies move
+    fn get_thin_wall() -> Shape {
+        Shape::Rect {
+            min: vec2(5., -2.),
+            max: vec2(5.1, 2.),
+        }
+    }
+
+    #[test]
+    fn circle_is_stopped_by_a_thin_wall() {
+        let circle = Shape::Circle {
+            center: Vec2::ZERO,
+            radius: 0.5,
+        };
+        let (t, normal) = get_time_of_impact(&circle, vec2(10., 0.), &get_thin_wall()).unwrap();
+        assert!((t - 0.45).abs() < 1e-4);
+        assert_close(normal, vec2(-1., 0.));
+    }
+
+    #[test]
+    fn box_is_stopped_by_a_thin_wall() {
+        let square = Shape::Rect {
+            min: vec2(-0.5, -0.5),
+            max: vec2(0.5, 0.5),
+        };
+        let (t, normal) = get_time_of_impact(&square, vec2(10., 0.), &get_thin_wall()).unwrap();
+        assert!((t - 0.45).abs() < 1e-4);
+        assert_close(normal, vec2(-1., 0.));
+    }
+
+    #[test]
+    fn circle_hits_the_corner_of_a_box() {
+        let circle = Shape::Circle {
+            center: vec2(0., 2.3),
+            radius: 0.5,
+        };
+        let (t, normal) = get_time_of_impact(&circle, vec2(10., 0.), &get_thin_wall()).unwrap();
+        // Touches the corner at (5, 2) from 0.3 above it
+        let hit_x = 5. - (0.5f32 * 0.5 - 0.3 * 0.3).sqrt();
+        assert!((t - hit_x / 10.).abs() < 1e-4);
+        assert_close(normal, (vec2(hit_x, 2.3) - vec2(5., 2.)).normalize());
+    }
+
+    #[test]
+    fn circle_is_stopped_by_a_circle() {
+        let circle = Shape::Circle {
+            center: Vec2::ZERO,
+            radius: 0.5,
+        };
+        let other = Shape::Circle {
+            center: vec2(5., 0.),
+            radius: 1.,
+        };
+        let (t, normal) = get_time_of_impact(&circle, vec2(10., 0.), &other).unwrap();
+        assert!((t - 0.35).abs() < 1e-4);
+        assert_close(normal, vec2(-1., 0.));
+    }
+
+    #[test]
+    fn nothing_is_hit_when_moving_past_or_not_far_enough() {
+        let circle = Shape::Circle {
+            center: Vec2::ZERO,
+            radius: 0.5,
+        };
+        let wall = get_thin_wall();
+        assert!(get_time_of_impact(&circle, vec2(4., 0.), &wall).is_none());
+        assert!(get_time_of_impact(&circle, vec2(-10., 0.), &wall).is_none());
+        assert!(get_time_of_impact(&circle, vec2(0., 10.), &wall).is_none());
+    }
+
+    #[test]
+    fn shapes_that_already_overlap_are_left_to_the_solver() {
+        let circle = Shape::Circle {
+            center: vec2(5., 0.),
+            radius: 0.5,
+        };
+        assert!(get_time_of_impact(&circle, vec2(10., 0.), &get_thin_wall()).is_none());
+    }
+
+    fn get_square(min: Vec2) -> Shape {
+        Shape::Polygon {
+            vertices: vec![
+                min,
+                min + vec2(1., 0.),
+                min + vec2(1., 1.),
+                min + vec2(0., 1.),
+            ],
+        }
+    }
+
+    #[test]
+    fn polygons_are_separated_along_the_axis_of_least_overlap() {
+        let contact = get_contact(&get_square(Vec2::ZERO), &get_square(vec2(0.8, 0.1))).unwrap();
+        assert_close(contact.normal, vec2(1., 0.));
+        assert!((contact.penetration - 0.2).abs() < 1e-4);
+
+        let contact = get_contact(&get_square(Vec2::ZERO), &get_square(vec2(-0.1, -0.7))).unwrap();
+        assert_close(contact.normal, vec2(0., -1.));
+        assert!((contact.penetration - 0.3).abs() < 1e-4);
+    }
+
+    #[test]
+    fn polygons_with_a_gap_between_them_dont_touch() {
+        let triangle = Shape::Polygon {
+            vertices: vec![vec2(0., 0.), vec2(2., 0.), vec2(0., 2.)],
+        };
+        // Inside the bounding box of the triangle, but past its slanted edge
+        assert!(get_contact(&triangle, &get_square(vec2(1.1, 1.1))).is_none());
+        assert!(get_contact(&triangle, &get_square(vec2(0.5, 0.5))).is_some());
+    }
+
+    #[test]
+    fn rectangles_collide_with_polygons_like_polygons_do() {
+        let rect = Shape::Rect {
+            min: Vec2::ZERO,
+            max: vec2(1., 1.),
+        };
+        let contact = get_contact(&rect, &get_square(vec2(0.8, 0.1))).unwrap();
+        assert_close(contact.normal, vec2(1., 0.));
+        assert!((contact.penetration - 0.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn circles_are_pushed_out_of_the_closest_corner_or_edge_of_a_polygon() {
+        let square = get_square(Vec2::ZERO);
+        let near_corner = Shape::Circle {
+            center: vec2(1.3, 1.4),
+            radius: 0.6,
+        };
+        let contact = get_contact(&square, &near_corner).unwrap();
+        assert_close(contact.normal, vec2(0.6, 0.8));
+        assert!((contact.penetration - 0.1).abs() < 1e-4);
+
+        let near_edge = Shape::Circle {
+            center: vec2(0.5, -0.2),
+            radius: 0.5,
+        };
+        let contact = get_contact(&near_edge, &square).unwrap();
+        assert_close(contact.normal, vec2(0., 1.));
+        assert!((contact.penetration - 0.3).abs() < 1e-4);
+
+        let apart = Shape::Circle {
+            center: vec2(1.5, 1.5),
+            radius: 0.6,
+        };
+        assert!(get_contact(&square, &apart).is_none());
+    }
+
+    #[test]
+    fn polygon_is_stopped_by_a_thin_wall() {
+        let square = get_square(vec2(-0.5, -0.5));
+        let (t, normal) = get_time_of_impact(&square, vec2(10., 0.), &get_thin_wall()).unwrap();
+        // Sampled, so only close to where it really hits
+        assert!((t - 0.45).abs() < 1e-3, "hit at {t}");
+        assert!(t <= 0.45);
+        assert_close(normal, vec2(-1., 0.));
+    }
+}
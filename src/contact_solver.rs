@@ -0,0 +1,552 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body_set::{BodyHandle, BodySet},
+    collision::{get_contact, Contact},
+    rigid_body::{get_combined_restitution, Format},
+    spatial_grid::SpatialGrid,
+};
+
+// Objects hitting each other slower than this don't bounce, so resting contacts stay at rest
+const RESTITUTION_THRESHOLD: f32 = 0.5;
+// Bodies get clamped to the edges of the world, so they count as touching an edge a bit before they overlap
+const BOUNDARY_SLOP: f32 = 0.01;
+// Overlap that is allowed to stay, so resting contacts don't jitter in and out of touching
+const LINEAR_SLOP: f32 = 0.01;
+// Fraction of the overlap removed each step, removing all of it at once overshoots in stacks
+const POSITION_CORRECTION: f32 = 0.8;
+// Size of the cells of the broad phase grid in metres. Bodies are only checked against the ones
+// that share a cell with them
+const BROAD_PHASE_CELL_SIZE: f32 = 2.;
+
+// Something that happened between two bodies during the last step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollisionEvent {
+    // `normal` points from `a` towards `b`, `impulse` is how hard they hit each other in N*s
+    BeginContact {
+        a: BodyHandle,
+        b: BodyHandle,
+        normal: Vec2,
+        impulse: f32,
+    },
+    EndContact {
+        a: BodyHandle,
+        b: BodyHandle,
+    },
+    TriggerEnter {
+        sensor: BodyHandle,
+        body: BodyHandle,
+    },
+    TriggerExit {
+        sensor: BodyHandle,
+        body: BodyHandle,
+    },
+}
+impl fmt::Display for CollisionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CollisionEvent::BeginContact { a, b, impulse, .. } => {
+                write!(f, "Body {a} hit body {b} with {} N*s", impulse.format(2))
+            }
+            CollisionEvent::EndContact { a, b } => {
+                write!(f, "Body {a} stopped touching body {b}")
+            }
+            CollisionEvent::TriggerEnter { sensor, body } => {
+                write!(f, "Body {body} entered sensor {sensor}")
+            }
+            CollisionEvent::TriggerExit { sensor, body } => {
+                write!(f, "Body {body} left sensor {sensor}")
+            }
+        }
+    }
+}
+
+// What a body is touching, the edges of the world can't move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Partner {
+    Body(usize),
+    Floor,
+    Ceiling,
+    LeftWall,
+    RightWall,
+}
+
+// A contact between body `b` and its partner, `normal` points from the partner towards `b`
+struct ContactConstraint {
+    partner: Partner,
+    b: usize,
+    normal: Vec2,
+    penetration: f32,
+    // 1 / (1 / m_a + 1 / m_b), how much mass the impulse acts on along the normal
+    effective_mass: f32,
+    // Relative velocity along the normal the bodies should separate with, from the restitution
+    velocity_bias: f32,
+    // Total impulse applied this step, this can't become negative as bodies can only push each other
+    impulse: f32,
+}
+
+// Sequential impulse solver, see "Iterative Dynamics with Temporal Coherence" by Erin Catto.
+// Instead of resolving each contact once, all contacts are solved for a number of iterations
+// so impulses can travel through stacks and chains of bodies.
+// Bodies are referred to by the index of their slot in the BodySet, these stay the same until
+// the body is removed, see forget_body
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ContactSolver {
+    pub velocity_iterations: usize,
+    pub warm_starting: bool,
+    // Pairs of bodies that never collide, by their indices with the lowest one first
+    ignored_pairs: HashSet<(usize, usize)>,
+    // The pair being picked in the ui
+    #[serde(skip)]
+    new_ignored_pair: (Option<BodyHandle>, Option<BodyHandle>),
+    // Impulse of every contact at the end of the last step
+    last_impulses: HashMap<(Partner, usize), f32>,
+    // Total impulse the edges of the world applied during the last step
+    boundary_impulse: Vec2,
+    // Pairs of bodies that touched or overlapped a sensor at the end of the last step, to find out what changed
+    last_touching: BTreeSet<(usize, usize)>,
+    last_overlapping: BTreeSet<(usize, usize)>,
+    // Only hold what happened during the last step, so they aren't saved
+    #[serde(skip)]
+    events: Vec<CollisionEvent>,
+    // (point, normal) of every contact that was solved
+    #[serde(skip)]
+    contact_points: Vec<(Vec2, Vec2)>,
+    // Bounding boxes of the bodies during the last step, filled again every step
+    #[serde(skip)]
+    grid: SpatialGrid,
+}
+impl ContactSolver {
+    pub fn new() -> Self {
+        Self {
+            velocity_iterations: 8,
+            warm_starting: true,
+            ignored_pairs: HashSet::new(),
+            new_ignored_pair: (None, None),
+            last_impulses: HashMap::new(),
+            boundary_impulse: Vec2::ZERO,
+            last_touching: BTreeSet::new(),
+            last_overlapping: BTreeSet::new(),
+            events: Vec::new(),
+            contact_points: Vec::new(),
+            grid: SpatialGrid::new(BROAD_PHASE_CELL_SIZE),
+        }
+    }
+
+    pub fn ignore_pair(&mut self, a: BodyHandle, b: BodyHandle) {
+        let (a, b) = (a.index(), b.index());
+        self.ignored_pairs.insert((a.min(b), a.max(b)));
+    }
+
+    // Slot indices of the ignored pairs, sorted so they come out the same every time
+    pub fn get_ignored_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs: Vec<(usize, usize)> = self.ignored_pairs.iter().copied().collect();
+        pairs.sort();
+        pairs
+    }
+
+    // Has to be called when a body gets removed, so a new body in the same slot starts out fresh
+    pub fn forget_body(&mut self, handle: BodyHandle) {
+        let index = handle.index();
+        self.ignored_pairs
+            .retain(|&(a, b)| a != index && b != index);
+        self.last_impulses
+            .retain(|&(partner, b), _| b != index && partner != Partner::Body(index));
+        self.last_touching
+            .retain(|&(a, b)| a != index && b != index);
+        self.last_overlapping
+            .retain(|&(a, b)| a != index && b != index);
+    }
+
+    // Checks the collision filters of both bodies and whether the pair is ignored
+    pub fn should_collide(&self, rigid_bodies: &BodySet, a: usize, b: usize) -> bool {
+        let filter_0 = rigid_bodies[a].get_collision_filter();
+        let filter_1 = rigid_bodies[b].get_collision_filter();
+        filter_0.can_collide(&filter_1) && !self.ignored_pairs.contains(&(a.min(b), a.max(b)))
+    }
+
+    // The edges of the world are the only thing that can change the total momentum of the bodies
+    pub fn get_boundary_impulse(&self) -> Vec2 {
+        self.boundary_impulse
+    }
+
+    // Events from the last call to solve
+    pub fn get_events(&self) -> &[CollisionEvent] {
+        &self.events
+    }
+
+    // (point, normal) of every contact solved during the last call to solve
+    pub fn get_contact_points(&self) -> &[(Vec2, Vec2)] {
+        &self.contact_points
+    }
+
+    pub fn get_grid(&self) -> &SpatialGrid {
+        &self.grid
+    }
+
+    // Returns the indices of every two bodies that are touching
+    // Contacts between two sleeping bodies aren't solved, so they aren't in here
+    pub fn solve(&mut self, rigid_bodies: &mut BodySet, world_size: Vec2) -> Vec<(usize, usize)> {
+        let mut contact_pairs = Vec::new();
+        let mut constraints = Vec::new();
+        // Normal of every contact, by the indices of its bodies
+        let mut touching = HashMap::new();
+        let mut overlapping = BTreeSet::new();
+
+        // Broad phase. Bodies outside the world are put in the cells along its edge, so the
+        // number of cells stays small whatever the bodies do
+        let shapes: Vec<_> = (0..rigid_bodies.slot_count())
+            .map(|i| {
+                let rb = rigid_bodies.get_at(i).filter(|rb| rb.get_enabled())?;
+                let shape = rb.get_shape()?;
+                let (min, max) = shape.get_bounds();
+                let bounds = (
+                    min.clamp(Vec2::ZERO, world_size),
+                    max.clamp(Vec2::ZERO, world_size),
+                );
+                Some((shape, bounds))
+            })
+            .collect();
+        self.grid.clear(BROAD_PHASE_CELL_SIZE);
+        for (i, entry) in shapes.iter().enumerate() {
+            if let Some((_, (min, max))) = entry {
+                self.grid.insert_box(i, *min, *max);
+            }
+        }
+
+        for b in 0..rigid_bodies.slot_count() {
+            let (Some(rb1), Some((shape_1, (min, max)))) = (rigid_bodies.get_at(b), &shapes[b])
+            else {
+                continue;
+            };
+
+            // Sorted, so contacts are solved in the same order as without the grid
+            let mut candidates: Vec<usize> = self
+                .grid
+                .get_in_box(*min, *max)
+                .filter(|&a| a < b)
+                .collect();
+            candidates.sort_unstable();
+            candidates.dedup();
+
+            for a in candidates {
+                let (Some(rb0), Some((shape_0, _))) = (rigid_bodies.get_at(a), &shapes[a]) else {
+                    continue;
+                };
+                if !self.should_collide(rigid_bodies, a, b) {
+                    continue;
+                }
+                let Some(contact) = get_contact(shape_0, shape_1) else {
+                    continue;
+                };
+                // Sensors only detect the overlap, the bodies go right through them
+                if rb0.get_sensor() || rb1.get_sensor() {
+                    overlapping.insert((a, b));
+                    continue;
+                }
+                contact_pairs.push((a, b));
+                touching.insert((a, b), contact.normal);
+                // Two sleeping bodies already lie still against each other
+                if rb0.get_sleeping() && rb1.get_sleeping() {
+                    continue;
+                }
+
+                let vel_along_normal = contact.normal.dot(rb1.get_vel() - rb0.get_vel());
+                let e = get_combined_restitution(rb0, rb1);
+                constraints.push(ContactConstraint {
+                    partner: Partner::Body(a),
+                    b,
+                    normal: contact.normal,
+                    penetration: contact.penetration,
+                    effective_mass: 1. / (1. / rb0.get_mass() + 1. / rb1.get_mass()),
+                    velocity_bias: get_velocity_bias(vel_along_normal, e),
+                    impulse: 0.,
+                });
+            }
+
+            if rb1.get_sleeping() {
+                continue;
+            }
+            for (partner, contact) in get_boundary_contacts(shape_1.get_bounds(), world_size) {
+                let vel_along_normal = contact.normal.dot(rb1.get_vel());
+                let e = rb1.get_restitution().unwrap_or(1.);
+                constraints.push(ContactConstraint {
+                    partner,
+                    b,
+                    normal: contact.normal,
+                    penetration: contact.penetration,
+                    effective_mass: rb1.get_mass(),
+                    velocity_bias: get_velocity_bias(vel_along_normal, e),
+                    impulse: 0.,
+                });
+            }
+        }
+
+        // Empty slots don't take part in any contact
+        let mut velocities: Vec<Vec2> = (0..rigid_bodies.slot_count())
+            .map(|i| rigid_bodies.get_at(i).map_or(Vec2::ZERO, |rb| rb.get_vel()))
+            .collect();
+        let inverse_masses: Vec<f32> = (0..rigid_bodies.slot_count())
+            .map(|i| rigid_bodies.get_at(i).map_or(0., |rb| 1. / rb.get_mass()))
+            .collect();
+
+        // Warm starting, start from the impulses of the last step as contacts rarely change much
+        if self.warm_starting {
+            for c in constraints.iter_mut() {
+                c.impulse = self
+                    .last_impulses
+                    .get(&(c.partner, c.b))
+                    .copied()
+                    .unwrap_or(0.);
+                apply_impulse(c, c.normal * c.impulse, &mut velocities, &inverse_masses);
+            }
+        }
+
+        for _ in 0..self.velocity_iterations {
+            for c in constraints.iter_mut() {
+                let partner_vel = match c.partner {
+                    Partner::Body(a) => velocities[a],
+                    _ => Vec2::ZERO,
+                };
+                let vel_along_normal = c.normal.dot(velocities[c.b] - partner_vel);
+                let delta = c.effective_mass * (c.velocity_bias - vel_along_normal);
+
+                // Clamp the total impulse instead of the change, so earlier iterations can be undone
+                let new_impulse = (c.impulse + delta).max(0.);
+                let impulse = c.normal * (new_impulse - c.impulse);
+                c.impulse = new_impulse;
+                apply_impulse(c, impulse, &mut velocities, &inverse_masses);
+            }
+        }
+
+        for c in constraints.iter() {
+            if let Partner::Body(a) = c.partner {
+                rigid_bodies[a].set_vel(velocities[a]);
+            }
+            rigid_bodies[c.b].set_vel(velocities[c.b]);
+        }
+        self.update_events(rigid_bodies, &constraints, &touching, overlapping);
+        // The normal points towards the second body, so it touches on its opposite side
+        self.contact_points = constraints
+            .iter()
+            .filter_map(|c| {
+                let shape = rigid_bodies[c.b].get_shape()?;
+                Some((shape.get_support_point(-c.normal), c.normal))
+            })
+            .collect();
+        self.boundary_impulse = constraints
+            .iter()
+            .filter(|c| !matches!(c.partner, Partner::Body(_)))
+            .fold(Vec2::ZERO, |sum, c| sum + c.normal * c.impulse);
+
+        // Velocities only stop bodies from sinking further into each other, so push them apart
+        // directly as well. Doing this with impulses would add energy and can make overlapping bodies fly apart
+        for c in constraints.iter() {
+            let correction = POSITION_CORRECTION * (c.penetration - LINEAR_SLOP).max(0.);
+            if correction == 0. {
+                continue;
+            }
+            match c.partner {
+                Partner::Body(a) => {
+                    let total_inverse_mass = inverse_masses[a] + inverse_masses[c.b];
+                    let move_a = -c.normal * correction * inverse_masses[a] / total_inverse_mass;
+                    let move_b = c.normal * correction * inverse_masses[c.b] / total_inverse_mass;
+                    let pos_a = rigid_bodies[a].get_pos();
+                    rigid_bodies[a].set_pos(pos_a + move_a);
+                    let pos_b = rigid_bodies[c.b].get_pos();
+                    rigid_bodies[c.b].set_pos(pos_b + move_b);
+                }
+                _ => {
+                    let pos = rigid_bodies[c.b].get_pos();
+                    rigid_bodies[c.b].set_pos(pos + c.normal * correction);
+                }
+            }
+        }
+        self.last_impulses = constraints
+            .iter()
+            .map(|c| ((c.partner, c.b), c.impulse))
+            .collect();
+
+        contact_pairs
+    }
+
+    // Compares the contacts and sensor overlaps to those of the last step
+    fn update_events(
+        &mut self,
+        rigid_bodies: &BodySet,
+        constraints: &[ContactConstraint],
+        touching: &HashMap<(usize, usize), Vec2>,
+        overlapping: BTreeSet<(usize, usize)>,
+    ) {
+        self.events.clear();
+        let touching_pairs: BTreeSet<(usize, usize)> = touching.keys().copied().collect();
+        // Bodies are only forgotten after they have been removed, so every index has a handle
+        let handles = |a: usize, b: usize| {
+            let handle_a = rigid_bodies.get_handle(a).expect("body was removed");
+            let handle_b = rigid_bodies.get_handle(b).expect("body was removed");
+            (handle_a, handle_b)
+        };
+
+        for &(a, b) in touching_pairs.difference(&self.last_touching) {
+            let impulse = constraints
+                .iter()
+                .find(|c| c.partner == Partner::Body(a) && c.b == b)
+                .map_or(0., |c| c.impulse);
+            let (a_handle, b_handle) = handles(a, b);
+            self.events.push(CollisionEvent::BeginContact {
+                a: a_handle,
+                b: b_handle,
+                normal: touching[&(a, b)],
+                impulse,
+            });
+        }
+        for &(a, b) in self.last_touching.difference(&touching_pairs) {
+            let (a, b) = handles(a, b);
+            self.events.push(CollisionEvent::EndContact { a, b });
+        }
+
+        // If both are sensors the first one counts as the sensor
+        let get_sensor_and_body = |a: usize, b: usize| {
+            let (a_handle, b_handle) = handles(a, b);
+            if rigid_bodies[a].get_sensor() {
+                (a_handle, b_handle)
+            } else {
+                (b_handle, a_handle)
+            }
+        };
+        for &(a, b) in overlapping.difference(&self.last_overlapping) {
+            let (sensor, body) = get_sensor_and_body(a, b);
+            self.events
+                .push(CollisionEvent::TriggerEnter { sensor, body });
+        }
+        for &(a, b) in self.last_overlapping.difference(&overlapping) {
+            let (sensor, body) = get_sensor_and_body(a, b);
+            self.events
+                .push(CollisionEvent::TriggerExit { sensor, body });
+        }
+
+        self.last_touching = touching_pairs;
+        self.last_overlapping = overlapping;
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui, rigid_bodies: &BodySet) {
+        ui.collapsing("Show ignored collisions", |ui| {
+            for (a, b) in self.get_ignored_pairs() {
+                let (Some(a_handle), Some(b_handle)) =
+                    (rigid_bodies.get_handle(a), rigid_bodies.get_handle(b))
+                else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    ui.label(format!("Body {a_handle} and body {b_handle}"));
+                    if ui.button("Remove").clicked() {
+                        self.ignored_pairs.remove(&(a, b));
+                    }
+                });
+            }
+
+            // Bodies that got removed since they were picked have to be picked again
+            let (a, b) = &mut self.new_ignored_pair;
+            for picked in [a, b] {
+                if picked.is_some_and(|handle| rigid_bodies.get(handle).is_none()) {
+                    *picked = None;
+                }
+            }
+            ui.horizontal(|ui| {
+                let (a, b) = &mut self.new_ignored_pair;
+                for (id, picked) in [("ignored pair a", a), ("ignored pair b", b)] {
+                    egui::ComboBox::from_id_source(id)
+                        .selected_text(
+                            picked.map_or("-".to_owned(), |handle| format!("Body {handle}")),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (handle, _) in rigid_bodies.iter_with_handles() {
+                                ui.selectable_value(picked, Some(handle), format!("Body {handle}"));
+                            }
+                        });
+                }
+                if let (Some(a), Some(b)) = self.new_ignored_pair {
+                    if ui.button("Ignore").clicked() && a != b {
+                        self.ignore_pair(a, b);
+                    }
+                }
+            });
+        });
+    }
+}
+
+fn apply_impulse(
+    c: &ContactConstraint,
+    impulse: Vec2,
+    velocities: &mut [Vec2],
+    inverse_masses: &[f32],
+) {
+    if let Partner::Body(a) = c.partner {
+        velocities[a] -= impulse * inverse_masses[a];
+    }
+    velocities[c.b] += impulse * inverse_masses[c.b];
+}
+
+fn get_velocity_bias(vel_along_normal: f32, restitution: f32) -> f32 {
+    if vel_along_normal < -RESTITUTION_THRESHOLD {
+        -restitution * vel_along_normal
+    } else {
+        0.
+    }
+}
+
+// The floor is 1 metre high, the other edges are the edges of the world
+fn get_boundary_contacts((min, max): (Vec2, Vec2), world_size: Vec2) -> Vec<(Partner, Contact)> {
+    let boundaries = [
+        (Partner::Floor, vec2(0., 1.), 1. - min.y),
+        (Partner::Ceiling, vec2(0., -1.), max.y - world_size.y),
+        (Partner::LeftWall, vec2(1., 0.), -min.x),
+        (Partner::RightWall, vec2(-1., 0.), max.x - world_size.x),
+    ];
+    boundaries
+        .into_iter()
+        .filter(|&(_, _, penetration)| penetration > -BOUNDARY_SLOP)
+        .map(|(partner, normal, penetration)| {
+            (
+                partner,
+                Contact {
+                    normal,
+                    penetration: penetration.max(0.),
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rigid_circle::RigidCircle;
+
+    #[test]
+    fn broad_phase_finds_every_touching_pair_once() {
+        let mut rigid_bodies = BodySet::new();
+        for (pos, radius) in [
+            (vec2(5., 5.), 1.),
+            // Touches the first one across the edge of a cell
+            (vec2(6.5, 5.), 1.),
+            (vec2(15., 15.), 1.),
+            (vec2(3.5, 5.), 1.),
+            // In a lot of cells, and touching the one at (15, 15)
+            (vec2(13., 12.), 3.),
+        ] {
+            rigid_bodies.insert(Box::new(RigidCircle::new(1., pos, radius)));
+        }
+        let mut solver = ContactSolver::new();
+        let mut pairs = solver.solve(&mut rigid_bodies, vec2(20., 20.));
+        pairs.sort_unstable();
+        assert_eq!(pairs, vec![(0, 1), (0, 3), (2, 4)]);
+    }
+}
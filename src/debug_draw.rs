@@ -0,0 +1,181 @@
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+
+use crate::{
+    body_set::BodySet, camera::Camera, contact_solver::ContactSolver, editor::get_body_bounds,
+    force_field::draw_arrow, rigid_body::RigidBody, spatial_grid::SpatialGrid, sph::SphFluid,
+};
+
+// Arrows are this many metres long for every m/s of velocity
+const VELOCITY_SCALE: f32 = 0.2;
+// Force arrows are this long for every m/s² the force accelerates the body with, so heavy
+// bodies don't get huge arrows
+const FORCE_SCALE: f32 = 0.2;
+// Contact normals are this many pixels long, whatever the zoom
+const NORMAL_LENGTH: f32 = 20.;
+const ID_FONT_SIZE: f32 = 20.;
+
+const VELOCITY_COLOR: Color = BLUE;
+// (name, colour) of every force arrow, in the order they are drawn
+const FORCE_COLORS: [(&str, Color); 6] = [
+    ("F_res", BLACK),
+    ("F_g", RED),
+    ("F_air", SKYBLUE),
+    ("F_spring", GREEN),
+    ("F_b", DARKBLUE),
+    ("F_field", PURPLE),
+];
+
+// Extra information drawn on top of the scene
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DebugDraw {
+    velocities: bool,
+    forces: bool,
+    contacts: bool,
+    bounds: bool,
+    centers_of_mass: bool,
+    grid: bool,
+    ids: bool,
+}
+impl DebugDraw {
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Debug draw", |ui| {
+            ui.checkbox(&mut self.velocities, "velocity vectors");
+            ui.checkbox(&mut self.forces, "force arrows");
+            if self.forces {
+                ui.horizontal_wrapped(|ui| {
+                    for (name, color) in FORCE_COLORS {
+                        ui.colored_label(to_egui_color(color), name);
+                    }
+                });
+            }
+            ui.checkbox(&mut self.contacts, "contact points and normals");
+            ui.checkbox(&mut self.bounds, "bounding boxes");
+            ui.checkbox(&mut self.centers_of_mass, "centre of mass");
+            ui.checkbox(&mut self.grid, "broad-phase grid").on_hover_text(
+                "Cells with bodies in them, bodies are only checked against the ones in the same cells. The cells of the fluid particles are blue",
+            );
+            ui.checkbox(&mut self.ids, "body ids");
+        });
+    }
+
+    pub fn draw(
+        &self,
+        camera: &Camera,
+        rigid_bodies: &BodySet,
+        solver: &ContactSolver,
+        sph: Option<&SphFluid>,
+    ) {
+        if self.grid {
+            draw_grid(camera, solver.get_grid(), DARKGRAY);
+            if let Some(sph) = sph {
+                draw_grid(camera, sph.get_grid(), SKYBLUE);
+            }
+        }
+        for (handle, rb) in rigid_bodies.iter_with_handles() {
+            if !rb.get_enabled() {
+                continue;
+            }
+            let center = rb.get_center_of_mass();
+            if self.bounds {
+                draw_bounds(camera, rb.as_ref());
+            }
+            if self.forces {
+                draw_forces(camera, rb.as_ref(), center);
+            }
+            if self.velocities {
+                draw_world_arrow(
+                    camera,
+                    center,
+                    rb.get_vel() * VELOCITY_SCALE,
+                    VELOCITY_COLOR,
+                );
+            }
+            if self.centers_of_mass {
+                let pos = camera.world_to_screen(center);
+                draw_circle(pos.x, pos.y, 4., WHITE);
+                draw_circle_lines(pos.x, pos.y, 4., 1.5, BLACK);
+            }
+            if self.ids {
+                let pos = camera.world_to_screen(center);
+                draw_text(
+                    &handle.to_string(),
+                    pos.x + 6.,
+                    pos.y - 6.,
+                    ID_FONT_SIZE,
+                    BLACK,
+                );
+            }
+        }
+        if self.contacts {
+            for &(point, normal) in solver.get_contact_points() {
+                let pos = camera.world_to_screen(point);
+                // The screen's y axis points down
+                let end = pos + vec2(normal.x, -normal.y) * NORMAL_LENGTH;
+                draw_arrow(pos, end, 2., ORANGE);
+                draw_circle(pos.x, pos.y, 3., RED);
+            }
+        }
+    }
+}
+
+fn draw_grid(camera: &Camera, grid: &SpatialGrid, color: Color) {
+    let cell_size = grid.get_cell_size();
+    let size = camera.metres_to_pixels(cell_size);
+    for (x, y) in grid.get_used_cells() {
+        let top_left = camera.world_to_screen(vec2(x as f32, (y + 1) as f32) * cell_size);
+        draw_rectangle_lines(top_left.x, top_left.y, size, size, 1., color);
+    }
+}
+
+fn draw_bounds(camera: &Camera, rb: &dyn RigidBody) {
+    let Some((min, max)) = get_body_bounds(rb) else {
+        return;
+    };
+    let top_left = camera.world_to_screen(vec2(min.x, max.y));
+    draw_rectangle_lines(
+        top_left.x,
+        top_left.y,
+        camera.metres_to_pixels(max.x - min.x),
+        camera.metres_to_pixels(max.y - min.y),
+        1.,
+        MAGENTA,
+    );
+}
+
+fn draw_forces(camera: &Camera, rb: &dyn RigidBody, center: Vec2) {
+    let forces = rb.get_forces();
+    // Gravity and buoyancy only store how strong they are
+    let vectors = [
+        Some(forces.f_res),
+        forces.f_g.map(|f_g| vec2(0., -f_g)),
+        forces.f_air,
+        forces.f_spring.map(|f_spring| vec2(0., f_spring)),
+        forces.f_buoyancy.map(|f_b| vec2(0., f_b)),
+        forces.f_field,
+    ];
+    let scale = FORCE_SCALE / rb.get_mass();
+    for (force, (_, color)) in vectors.into_iter().zip(FORCE_COLORS) {
+        if let Some(force) = force {
+            draw_world_arrow(camera, center, force * scale, color);
+        }
+    }
+}
+
+// Arrow from `start` along `vector`, both in metres
+fn draw_world_arrow(camera: &Camera, start: Vec2, vector: Vec2, color: Color) {
+    if vector == Vec2::ZERO {
+        return;
+    }
+    draw_arrow(
+        camera.world_to_screen(start),
+        camera.world_to_screen(start + vector),
+        2.,
+        color,
+    );
+}
+
+fn to_egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, a]: [u8; 4] = color.into();
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+}
@@ -0,0 +1,179 @@
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+
+use crate::{
+    camera::Camera,
+    rigid_body::{RigidBody, RigidBodyType},
+};
+
+// Size of the handles in pixels, they stay the same size when zooming
+const HANDLE_SIZE: f32 = 10.;
+// How far above the body the rotation handle is, in pixels
+const ROTATE_HANDLE_OFFSET: f32 = 30.;
+// Bodies can't be resized smaller than this many metres
+const MIN_BODY_SIZE: f32 = 0.2;
+
+// What the selected body is being dragged by
+#[derive(Debug, Clone, Copy)]
+enum GizmoDrag {
+    // From the mouse to the position of the body
+    Move { offset: Vec2 },
+    // The bottom left corner stays in place while the top right one follows the mouse
+    Resize { anchor: Vec2 },
+    // Angle of the mouse around the centre of the body during the last frame
+    Rotate { center: Vec2, last_angle: f32 },
+}
+
+// Changes the bodies of a scene while the simulation is paused, and saves them as a scene file
+pub struct Editor {
+    pub enabled: bool,
+    drag: Option<GizmoDrag>,
+    // Name of the scene file to save to, without the extension
+    file_name: String,
+    // Outcome of the last time the scene was saved
+    pub message: Option<String>,
+}
+impl Editor {
+    pub fn new(file_name: &str) -> Self {
+        Self {
+            enabled: false,
+            drag: None,
+            file_name: file_name.to_owned(),
+            message: None,
+        }
+    }
+
+    // Grabs a handle or the body itself, returns false if the mouse isn't on any of them
+    pub fn start_drag(&mut self, rb: &dyn RigidBody, mouse_pos: Vec2, camera: &Camera) -> bool {
+        let Some((min, max)) = get_body_bounds(rb) else {
+            return false;
+        };
+        let mouse_screen: Vec2 = mouse_position().into();
+        let is_on = |handle: Vec2| mouse_screen.distance(handle) < HANDLE_SIZE;
+
+        self.drag = if can_rotate(rb) && is_on(get_rotate_handle(min, max, camera)) {
+            let center = (min + max) * 0.5;
+            Some(GizmoDrag::Rotate {
+                center,
+                last_angle: get_angle(mouse_pos - center),
+            })
+        } else if is_on(camera.world_to_screen(max)) {
+            Some(GizmoDrag::Resize { anchor: min })
+        } else if rb.contains_point(mouse_pos) {
+            Some(GizmoDrag::Move {
+                offset: rb.get_pos() - mouse_pos,
+            })
+        } else {
+            None
+        };
+        self.drag.is_some()
+    }
+
+    pub fn update_drag(&mut self, rb: &mut dyn RigidBody, mouse_pos: Vec2) {
+        let Some(drag) = &mut self.drag else {
+            return;
+        };
+        match drag {
+            GizmoDrag::Move { offset } => rb.translate(mouse_pos + *offset - rb.get_pos()),
+            GizmoDrag::Resize { anchor } => {
+                let Some((min, max)) = get_body_bounds(rb) else {
+                    return;
+                };
+                let new_max = mouse_pos.max(*anchor + MIN_BODY_SIZE);
+                rb.scale((new_max - *anchor) / (max - min));
+                // Scaling happens around the position, so move the corner back to where it was
+                if let Some((min, _)) = get_body_bounds(rb) {
+                    rb.translate(*anchor - min);
+                }
+            }
+            GizmoDrag::Rotate { center, last_angle } => {
+                let angle = get_angle(mouse_pos - *center);
+                rb.rotate(angle - *last_angle);
+                *last_angle = angle;
+            }
+        }
+        // It has to react to where it was put once the simulation continues
+        rb.set_sleeping(false);
+        rb.set_sleep_timer(0.);
+    }
+
+    pub fn stop_drag(&mut self) {
+        self.drag = None;
+    }
+
+    pub fn draw_gizmo(&self, rb: &dyn RigidBody, camera: &Camera) {
+        let Some((min, max)) = get_body_bounds(rb) else {
+            return;
+        };
+        let corner = camera.world_to_screen(max);
+        draw_rectangle(
+            corner.x - HANDLE_SIZE * 0.5,
+            corner.y - HANDLE_SIZE * 0.5,
+            HANDLE_SIZE,
+            HANDLE_SIZE,
+            ORANGE,
+        );
+        if can_rotate(rb) {
+            let handle = get_rotate_handle(min, max, camera);
+            let top = camera.world_to_screen(vec2((min.x + max.x) * 0.5, max.y));
+            draw_line(top.x, top.y, handle.x, handle.y, 2., ORANGE);
+            draw_circle(handle.x, handle.y, HANDLE_SIZE * 0.5, ORANGE);
+        }
+    }
+
+    // Returns the file name to save the scene as when the button gets clicked
+    pub fn update_ui(&mut self, ui: &mut Ui) -> Option<String> {
+        ui.heading("Editor");
+        ui.checkbox(&mut self.enabled, "edit mode").on_hover_text(
+            "Pauses the simulation. Drag bodies to move them, the square to resize them \
+             and the circle to rotate polygons",
+        );
+        if !self.enabled {
+            self.drag = None;
+            return None;
+        }
+        ui.label("The window of the selected body shows its properties");
+        let mut save = None;
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.add(egui::TextEdit::singleline(&mut self.file_name).desired_width(100.));
+        });
+        if ui
+            .button("Save scene")
+            .on_hover_text("Save the scene the way it is now to the scenes directory")
+            .clicked()
+        {
+            save = Some(self.file_name.clone());
+        }
+        if let Some(message) = &self.message {
+            ui.label(message);
+        }
+        save
+    }
+}
+
+// Smallest box around the body as (min, max). Springs don't have a shape, so it's their weight
+pub fn get_body_bounds(rb: &dyn RigidBody) -> Option<(Vec2, Vec2)> {
+    match rb.get_shape() {
+        Some(shape) => Some(shape.get_bounds()),
+        None => {
+            let size = rb.get_size()?;
+            let pos = rb.get_pos();
+            Some((vec2(pos.x, pos.y - size.y), vec2(pos.x + size.x, pos.y)))
+        }
+    }
+}
+
+fn can_rotate(rb: &dyn RigidBody) -> bool {
+    rb.get_type() == RigidBodyType::Polygon
+}
+
+// Above the middle of the top edge, on the screen
+fn get_rotate_handle(min: Vec2, max: Vec2, camera: &Camera) -> Vec2 {
+    let top = camera.world_to_screen(vec2((min.x + max.x) * 0.5, max.y));
+    top - vec2(0., ROTATE_HANDLE_OFFSET)
+}
+
+fn get_angle(v: Vec2) -> f32 {
+    v.y.atan2(v.x)
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+
+use egui_macroquad::egui::{
+    self,
+    plot::{Legend, Line, Plot, PlotPoints},
+    Ui,
+};
+use macroquad::prelude::*;
+
+use crate::{body_set::BodySet, engine::Variables};
+
+// Potential energy is measured from the top of the floor
+const FLOOR_HEIGHT: f32 = 1.;
+// Seconds of history shown in the graph
+const HISTORY: f32 = 10.;
+// Drift is relative to the starting energy, but at least to this many joules, so almost no
+// energy doesn't count as a huge drift
+const MIN_REFERENCE_ENERGY: f32 = 1.;
+
+// Totals over every enabled body
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnergyReport {
+    pub kinetic: f32,
+    pub gravitational: f32,
+    pub spring: f32,
+    pub momentum: Vec2,
+    // Around the origin. Bodies don't spin, so it only comes from how they move
+    pub angular_momentum: f32,
+}
+impl EnergyReport {
+    pub fn measure(rigid_bodies: &BodySet, vars: &Variables) -> Self {
+        let mut report = Self::default();
+        for rb in rigid_bodies.iter().filter(|rb| rb.get_enabled()) {
+            let mass = rb.get_mass();
+            let vel = rb.get_vel();
+            let center = rb.get_center_of_mass();
+            report.kinetic += 0.5 * mass * vel.length_squared();
+            // Springs aren't pulled down by gravity
+            if let (Some(g), Some(_)) = (vars.g, rb.get_forces().f_g) {
+                report.gravitational += mass * g * (center.y - FLOOR_HEIGHT);
+            }
+            report.spring += rb.get_spring_energy();
+            report.momentum += mass * vel;
+            report.angular_momentum += mass * center.perp_dot(vel);
+        }
+        report
+    }
+
+    pub fn get_total(&self) -> f32 {
+        self.kinetic + self.gravitational + self.spring
+    }
+}
+
+// Keeps track of the energy and momentum over time, to see whether the simulation gains or
+// loses energy it shouldn't
+pub struct EnergyMonitor {
+    report: EnergyReport,
+    // (time, report) after every step
+    history: VecDeque<(f32, EnergyReport)>,
+    time: f32,
+    // Total energy and number of bodies when it started measuring. Adding or removing bodies
+    // changes the energy, so it starts again then
+    baseline: Option<(f32, usize)>,
+    // Fraction of the starting energy the total may drift before it gets flagged
+    drift_threshold: f32,
+}
+impl EnergyMonitor {
+    pub fn new() -> Self {
+        Self {
+            report: EnergyReport::default(),
+            history: VecDeque::new(),
+            time: 0.,
+            baseline: None,
+            drift_threshold: 0.01,
+        }
+    }
+
+    // Has to be called right after every step
+    pub fn record_step(&mut self, delta_time: f32, rigid_bodies: &BodySet, vars: &Variables) {
+        self.time += delta_time;
+        self.report = EnergyReport::measure(rigid_bodies, vars);
+        let body_count = rigid_bodies.iter().count();
+        if self
+            .baseline
+            .is_none_or(|(_, baseline_count)| baseline_count != body_count)
+        {
+            self.baseline = Some((self.report.get_total(), body_count));
+        }
+
+        self.history.push_back((self.time, self.report));
+        while self
+            .history
+            .front()
+            .is_some_and(|(time, _)| *time < self.time - HISTORY)
+        {
+            self.history.pop_front();
+        }
+    }
+
+    // Total energy now compared to when it started measuring, as a fraction
+    pub fn get_drift(&self) -> Option<f32> {
+        let (baseline, _) = self.baseline?;
+        Some((self.report.get_total() - baseline) / baseline.abs().max(MIN_REFERENCE_ENERGY))
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Show energy", |ui| {
+            let report = self.report;
+            ui.label(format!("Kinetic: {:.2} J", report.kinetic));
+            ui.label(format!("Gravitational: {:.2} J", report.gravitational))
+                .on_hover_text("Measured from the top of the floor");
+            ui.label(format!("Spring: {:.2} J", report.spring));
+            ui.label(format!("Total: {:.2} J", report.get_total()));
+            ui.label(format!(
+                "Momentum: ({:.2}, {:.2}) kg*m/s",
+                report.momentum.x, report.momentum.y
+            ));
+            ui.label(format!(
+                "Angular momentum: {:.2} kg*m²/s",
+                report.angular_momentum
+            ))
+            .on_hover_text("Around the origin");
+
+            if let Some(drift) = self.get_drift() {
+                let text = format!("Energy drift: {:.2} %", drift * 100.);
+                if drift.abs() > self.drift_threshold {
+                    ui.colored_label(egui::Color32::RED, text).on_hover_text(
+                        "The total energy changed more than the threshold since it started measuring",
+                    );
+                } else {
+                    ui.label(text);
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.label("Threshold:");
+                let mut percent = self.drift_threshold * 100.;
+                ui.add(
+                    egui::DragValue::new(&mut percent)
+                        .speed(0.1)
+                        .clamp_range(0.0..=100.)
+                        .suffix(" %"),
+                );
+                self.drift_threshold = percent / 100.;
+                if ui
+                    .button("Restart")
+                    .on_hover_text("Measure the drift from the energy there is now")
+                    .clicked()
+                {
+                    self.baseline = None;
+                }
+            });
+
+            let get_line = |name: &str, get_value: fn(&EnergyReport) -> f32| {
+                let points: PlotPoints = self
+                    .history
+                    .iter()
+                    .map(|(time, report)| [*time as f64, get_value(report) as f64])
+                    .collect();
+                Line::new(points).name(name)
+            };
+            Plot::new("energy plot")
+                .legend(Legend::default())
+                .height(120.)
+                .show(ui, |plot_ui| {
+                    plot_ui.line(get_line("kinetic", |report| report.kinetic));
+                    plot_ui.line(get_line("gravitational", |report| report.gravitational));
+                    plot_ui.line(get_line("spring", |report| report.spring));
+                    plot_ui.line(get_line("total", EnergyReport::get_total));
+                });
+        });
+    }
+}
@@ -1,129 +1,732 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::Path,
+};
+
 use egui_macroquad::egui::{self, Ui};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    rigid_body::{Format, RigidBodies},
-    scenes::Scene,
-    SCREEN_SIZE,
+    body_set::{BodyHandle, BodySet},
+    camera::Camera,
+    contact_solver::{CollisionEvent, ContactSolver},
+    debug_draw::DebugDraw,
+    editor::Editor,
+    energy::EnergyMonitor,
+    fluid::FluidVolume,
+    force_field::ForceField,
+    mouse_joint::MouseJoint,
+    plot::PlotPanel,
+    replay::{hash_part, Replay, ReplayEvent, ReplayPart, ReplayPlayer, ReplayRecorder},
+    rigid_body::{Format, RigidBody},
+    scenes::{BodyDescription, Scene, SceneList},
+    snapshot::Snapshot,
+    tools::{Tool, Toolbar},
+    trails::{draw_predicted_path, Prediction, Trails},
+    trajectory::TrajectoryRecorder,
+    world::{StepReport, World},
 };
 
 const TIME_INCREMENT: f32 = 0.01;
+// Speed limit used when it gets turned on in the ui, in m/s
+const DEFAULT_SPEED_LIMIT: f32 = 100.;
+// Older lines get removed from the collision event log
+const MAX_LOGGED_EVENTS: usize = 200;
+// Saved states and replays are written to and read from these files in the working directory
+const SNAPSHOT_FILE: &str = "snapshot.ron";
+const REPLAY_FILE: &str = "replay.ron";
+const TRAJECTORY_CSV_FILE: &str = "trajectory.csv";
+const TRAJECTORY_BINARY_FILE: &str = "trajectory.bin";
+// Length of the steps used to predict paths, in seconds
+const PREDICTION_STEP: f32 = 1. / 60.;
 
 pub struct Engine {
     scene: Scene,
-    rigid_bodies: RigidBodies,
-    world_size: Vec2,
-    vars: Variables,
+    scene_list: SceneList,
+    world: World,
+    step_report: StepReport,
+    limited_steps: usize,
+    event_log: VecDeque<String>,
+    log_contacts: bool,
+    // How many bodies entered each sensor
+    sensor_enter_counts: BTreeMap<BodyHandle, usize>,
+    camera: Camera,
+    toolbar: Toolbar,
+    editor: Editor,
+    selected: Option<BodyHandle>,
+    // Clicks on the ui shouldn't spawn or remove bodies behind it
+    pointer_over_ui: bool,
     time_mult: f32,
     pause: bool,
     time_step_mode_enabled: bool,
     show_entity_ui: bool,
+    show_field_arrows: bool,
     time_passed: f32,
+    recorder: Option<ReplayRecorder>,
+    player: Option<ReplayPlayer>,
+    trajectory: Option<TrajectoryRecorder>,
+    plot: PlotPanel,
+    energy: EnergyMonitor,
+    debug_draw: DebugDraw,
+    trails: Trails,
+    prediction: Prediction,
+    // Settings for the next trajectory recording
+    trajectory_interval: u32,
+    trajectory_binary: bool,
+    // Outcome of the last time a state or replay was saved or loaded
+    file_message: Option<String>,
 }
 impl Engine {
-    pub fn new(scene: Scene) -> Self {
-        Self {
-            scene,
-            rigid_bodies: scene.get_rigid_bodies(),
-            world_size: scene.get_world_size(),
-            vars: scene.get_variables(),
+    pub fn new(scene: Scene, scene_list: SceneList) -> Self {
+        let mut engine = Self {
+            world: World {
+                rigid_bodies: BodySet::new(),
+                world_size: scene.get_world_size(),
+                vars: scene.get_variables(),
+                sph: scene.get_sph_fluid(),
+                solver: ContactSolver::new(),
+                speed_limit: None,
+                sleeping_enabled: true,
+                mouse_joint: None,
+            },
+            step_report: StepReport::default(),
+            limited_steps: 0,
+            event_log: VecDeque::new(),
+            log_contacts: false,
+            sensor_enter_counts: BTreeMap::new(),
+            camera: Camera::new(scene.get_world_size()),
+            toolbar: Toolbar::new(),
+            editor: Editor::new(scene.get_file_name()),
+            selected: None,
+            pointer_over_ui: false,
             time_mult: 1.,
             pause: false,
             time_step_mode_enabled: false,
             show_entity_ui: false,
+            show_field_arrows: false,
             time_passed: 0.,
+            recorder: None,
+            player: None,
+            trajectory: None,
+            plot: PlotPanel::new(),
+            energy: EnergyMonitor::new(),
+            debug_draw: DebugDraw::default(),
+            trails: Trails::new(),
+            prediction: Prediction::new(),
+            trajectory_interval: 1,
+            trajectory_binary: false,
+            file_message: None,
+            scene,
+            scene_list,
+        };
+        engine.load_bodies();
+        engine
+    }
+
+    // Starts `scene` from the beginning
+    fn start_scene(&mut self, scene: Scene) {
+        let scene_list = std::mem::take(&mut self.scene_list);
+        let editing = self.editor.enabled;
+        let plotting = self.plot.open;
+        let debug_draw = self.debug_draw;
+        let trails_enabled = self.trails.enabled;
+        let prediction = self.prediction.clone();
+        *self = Engine::new(scene, scene_list);
+        self.editor.enabled = editing;
+        self.plot.open = plotting;
+        self.debug_draw = debug_draw;
+        self.trails.enabled = trails_enabled;
+        self.prediction = prediction;
+    }
+
+    pub fn get_snapshot(&self) -> Snapshot {
+        Snapshot {
+            scene_file_name: self.scene.get_file_name().to_owned(),
+            scene: self.scene.clone(),
+            rigid_bodies: self.world.rigid_bodies.clone(),
+            world_size: self.world.world_size,
+            vars: self.world.vars.clone(),
+            sph: self.world.sph.clone(),
+            solver: self.world.solver.clone(),
+            speed_limit: self.world.speed_limit,
+            sensor_enter_counts: self.sensor_enter_counts.clone(),
+            sleeping_enabled: self.world.sleeping_enabled,
+            time_mult: self.time_mult,
+            time_passed: self.time_passed,
+        }
+    }
+
+    // Continues from the saved state, keeping the camera and the ui as they are
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        if snapshot.world_size != self.world.world_size {
+            self.camera = Camera::new(snapshot.world_size);
+        }
+        self.scene = snapshot.scene;
+        self.world.rigid_bodies = snapshot.rigid_bodies;
+        self.world.world_size = snapshot.world_size;
+        self.world.vars = snapshot.vars;
+        self.world.sph = snapshot.sph;
+        self.world.solver = snapshot.solver;
+        self.world.speed_limit = snapshot.speed_limit;
+        self.sensor_enter_counts = snapshot.sensor_enter_counts;
+        self.world.sleeping_enabled = snapshot.sleeping_enabled;
+        self.time_mult = snapshot.time_mult;
+        self.time_passed = snapshot.time_passed;
+
+        // These refer to bodies that might not exist anymore
+        self.selected = None;
+        self.world.mouse_joint = None;
+        self.step_report = StepReport::default();
+        self.limited_steps = 0;
+        self.event_log.clear();
+    }
+
+    // Replaces all bodies with the ones from the scene
+    fn load_bodies(&mut self) {
+        for (handle, _) in self.world.rigid_bodies.iter_with_handles() {
+            self.world.solver.forget_body(handle);
+        }
+        self.world.rigid_bodies = BodySet::new();
+        self.sensor_enter_counts.clear();
+
+        let handles: Vec<BodyHandle> = self
+            .scene
+            .get_rigid_bodies()
+            .into_iter()
+            .map(|rb| self.add_body(rb))
+            .collect();
+        for (a, b) in self.scene.get_ignored_pairs() {
+            self.world.solver.ignore_pair(handles[a], handles[b]);
         }
     }
+
+    pub fn add_body(&mut self, body: Box<dyn RigidBody>) -> BodyHandle {
+        self.world.rigid_bodies.insert(body)
+    }
+
+    // Returns None if the body was already removed
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<Box<dyn RigidBody>> {
+        let body = self.world.rigid_bodies.remove(handle)?;
+        self.world.solver.forget_body(handle);
+        self.sensor_enter_counts.remove(&handle);
+        if self.selected == Some(handle) {
+            self.selected = None;
+        }
+        Some(body)
+    }
+
+    pub fn get_body(&self, handle: BodyHandle) -> Option<&dyn RigidBody> {
+        self.world.rigid_bodies.get(handle)
+    }
+
+    // The body drawn on top at `point`
+    fn get_body_at(&self, point: Vec2) -> Option<BodyHandle> {
+        self.world
+            .rigid_bodies
+            .iter_with_handles()
+            .filter(|(_, rb)| rb.get_enabled() && rb.contains_point(point))
+            .map(|(handle, _)| handle)
+            .last()
+    }
+
+    // Adds a copy of the selected body right next to it and selects the copy
+    fn duplicate_selected(&mut self) {
+        let Some(rb) = self.selected.and_then(|handle| self.get_body(handle)) else {
+            return;
+        };
+        let mut copy = rb.duplicate();
+        let width = rb.get_shape().map_or(0., |shape| {
+            let (min, max) = shape.get_bounds();
+            max.x - min.x
+        });
+        copy.set_pos(rb.get_pos() + vec2(width, 0.));
+        copy.set_sleeping(false);
+        self.selected = Some(self.add_body(copy));
+    }
+
     pub fn update(&mut self) {
         self.update_based_on_ui();
+        self.update_camera();
+        if self.player.is_some() {
+            if !self.pause {
+                self.play_replay_step();
+            }
+            return;
+        }
+        self.update_tools();
 
-        //apply forces on the rigidbodies
-        if !self.pause && !self.time_step_mode_enabled {
-            let delta_time = self.time_mult * get_frame_time();
-            self.rigid_bodies.iter_mut().for_each(|rb| {
-                if rb.get_enabled() {
-                    rb.apply_forces(self.vars, delta_time, self.scene.get_world_size());
-                }
+        let delta_time = self.time_mult * get_frame_time();
+        // Nothing moves on its own while editing
+        if !self.pause && !self.time_step_mode_enabled && !self.editor.enabled {
+            self.advance(delta_time);
+        }
+        self.update_prediction();
+    }
+
+    // Steps the simulation, recording what the user changed since the last step if there is a recording
+    pub fn advance(&mut self, delta_time: f32) {
+        if let Some(mut recorder) = self.recorder.take() {
+            let result = self.hash_replay_parts().map(|hashes| {
+                recorder.record_step(delta_time, hashes, |part| self.get_replay_event(part));
+                recorder
             });
+            self.keep_recording(result);
         }
 
-        self.resolve_collisions();
+        let contact_pairs = self.step(delta_time);
+        self.world.update_sleeping(&contact_pairs, delta_time);
+        self.record_step(delta_time);
+
+        if let Some(mut recorder) = self.recorder.take() {
+            let result = self.hash_replay_parts().map(|hashes| {
+                recorder.skip_changes(hashes);
+                recorder
+            });
+            self.keep_recording(result);
+        }
     }
-    pub fn draw(&self) {
-        self.scene.draw_background();
 
-        // Draw rigidbodies
-        let metre_in_pixels = SCREEN_SIZE / self.scene.get_world_size();
-        self.rigid_bodies.iter().for_each(|rb| {
-            if rb.get_enabled() {
-                rb.draw(metre_in_pixels);
-            }
+    // Puts the recorder back, or stops recording if the engine couldn't be hashed
+    fn keep_recording(&mut self, result: Result<ReplayRecorder, String>) {
+        match result {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(err) => self.file_message = Some(format!("Stopped recording: {err}")),
+        }
+    }
+
+    // Keeps what happened during the step for the trajectory, the plot and the energy graph
+    fn record_step(&mut self, delta_time: f32) {
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.record_step(delta_time, &self.world.rigid_bodies);
+        }
+        self.plot.record_step(delta_time, &self.world.rigid_bodies);
+        self.energy
+            .record_step(delta_time, &self.world.rigid_bodies, &self.world.vars);
+        self.trails
+            .record_step(delta_time, &self.world.rigid_bodies);
+    }
+
+    // Predicts the path again when anything it depends on changed since the last frame
+    fn update_prediction(&mut self) {
+        if !self.prediction.enabled {
+            return;
+        }
+        let preview = self.toolbar.get_preview_body(self.camera.get_mouse_pos());
+        let followed = self
+            .world
+            .mouse_joint
+            .map(|joint| joint.body)
+            .or(self.selected);
+        let duration = self.prediction.duration;
+        let input = self.hash_replay_parts().and_then(|parts| {
+            let preview_state = preview.as_ref().map(|body| body.get_state());
+            hash_part(&(parts, preview_state, followed, duration))
         });
+        let Ok(input) = input else {
+            self.prediction.clear();
+            return;
+        };
+        let world = &self.world;
+        self.prediction
+            .update(input, || predict_path(world, preview, followed, duration));
+    }
+
+    // Hash of everything the user can change that affects the simulation, in the order of
+    // ReplayPart::ALL
+    fn hash_replay_parts(&self) -> Result<Vec<Option<u64>>, String> {
+        ReplayPart::ALL
+            .into_iter()
+            .map(|part| match part {
+                ReplayPart::Bodies => hash_part(&self.world.rigid_bodies).map(Some),
+                ReplayPart::Variables => hash_part(&self.world.vars).map(Some),
+                ReplayPart::Solver => hash_part(&self.world.solver).map(Some),
+                ReplayPart::SphSettings => self
+                    .world
+                    .sph
+                    .as_ref()
+                    .map(|sph| hash_part(&sph.get_settings()))
+                    .transpose(),
+                ReplayPart::SpeedLimit => hash_part(&self.world.speed_limit).map(Some),
+                ReplayPart::SleepingEnabled => hash_part(&self.world.sleeping_enabled).map(Some),
+                ReplayPart::MouseJoint => hash_part(&self.world.mouse_joint).map(Some),
+            })
+            .collect()
     }
 
-    fn resolve_collisions(&mut self) {
-        for j in 0..self.rigid_bodies.len() {
-            for i in j + 1..self.rigid_bodies.len() {
+    fn get_replay_event(&self, part: ReplayPart) -> Option<ReplayEvent> {
+        Some(match part {
+            ReplayPart::Bodies => ReplayEvent::Bodies(self.world.rigid_bodies.clone()),
+            ReplayPart::Variables => ReplayEvent::Variables(self.world.vars.clone()),
+            ReplayPart::Solver => ReplayEvent::Solver(Box::new(self.world.solver.clone())),
+            ReplayPart::SphSettings => {
+                ReplayEvent::SphSettings(self.world.sph.as_ref()?.get_settings())
+            }
+            ReplayPart::SpeedLimit => ReplayEvent::SpeedLimit(self.world.speed_limit),
+            ReplayPart::SleepingEnabled => {
+                ReplayEvent::SleepingEnabled(self.world.sleeping_enabled)
+            }
+            ReplayPart::MouseJoint => ReplayEvent::MouseJoint(self.world.mouse_joint),
+        })
+    }
 
-                // Check if objects are colliding, continue to the next iteration if not
-                if !self.rigid_bodies[j].colliding(&self.rigid_bodies[i]) {
-                    continue;
+    fn apply_replay_event(&mut self, event: ReplayEvent) {
+        match event {
+            ReplayEvent::Bodies(rigid_bodies) => self.world.rigid_bodies = rigid_bodies,
+            ReplayEvent::Variables(vars) => self.world.vars = vars,
+            ReplayEvent::Solver(solver) => self.world.solver = *solver,
+            ReplayEvent::SphSettings(settings) => {
+                if let Some(sph) = &mut self.world.sph {
+                    sph.set_settings(settings);
                 }
-                //draw_rectangle(300., 300., 100., 100., BLACK);
+            }
+            ReplayEvent::SpeedLimit(speed_limit) => self.world.speed_limit = speed_limit,
+            ReplayEvent::SleepingEnabled(enabled) => self.world.sleeping_enabled = enabled,
+            ReplayEvent::MouseJoint(joint) => self.world.mouse_joint = joint,
+        }
+    }
 
-                let rb0 = &self.rigid_bodies[j];
-                let rb1 = &self.rigid_bodies[i];
+    fn get_final_positions(&self) -> Vec<Vec2> {
+        self.world
+            .rigid_bodies
+            .iter()
+            .map(|rb| rb.get_pos())
+            .collect()
+    }
 
-                // Collision normal, the direction in which the impulse will be applied
-                let normal = (rb1.get_pos() - rb0.get_pos()).normalize();
+    // Keeps the state of every body every `sample_interval` steps from now on
+    pub fn start_trajectory(&mut self, sample_interval: u32) {
+        self.trajectory = Some(TrajectoryRecorder::new(sample_interval));
+    }
 
-                // Calculate relative velocity
-                let relative_vel = rb1.get_vel() - rb0.get_vel();
+    pub fn take_trajectory(&mut self) -> Option<TrajectoryRecorder> {
+        self.trajectory.take()
+    }
+
+    fn stop_trajectory(&mut self) {
+        let Some(trajectory) = self.take_trajectory() else {
+            return;
+        };
+        let mut result = trajectory.save_csv(Path::new(TRAJECTORY_CSV_FILE));
+        let mut files = TRAJECTORY_CSV_FILE.to_owned();
+        if self.trajectory_binary {
+            result = result.and(trajectory.save_binary(Path::new(TRAJECTORY_BINARY_FILE)));
+            files += &format!(" and {TRAJECTORY_BINARY_FILE}");
+        }
+        self.file_message = Some(match result {
+            Ok(()) => format!(
+                "Exported {} samples to {files}",
+                trajectory.get_sample_count()
+            ),
+            Err(err) => err,
+        });
+    }
 
-                // Calculate relative velocity in terms of the normal direction
-                let vel_along_normal = normal.dot(relative_vel);
-                if vel_along_normal > 0. {
-                    continue; // Only resolve collision if objects are moving towards each other
+    fn start_recording(&mut self) {
+        match self.hash_replay_parts() {
+            Ok(hashes) => {
+                self.recorder = Some(ReplayRecorder::new(self.get_snapshot(), hashes));
+                self.file_message = None;
+            }
+            Err(err) => self.file_message = Some(err),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        let replay = recorder.finish(self.get_final_positions());
+        let steps = replay.get_step_count();
+        self.file_message = Some(match replay.save(Path::new(REPLAY_FILE)) {
+            Ok(()) => format!("Saved {steps} steps to {REPLAY_FILE}"),
+            Err(err) => err,
+        });
+    }
+
+    fn start_replay(&mut self) {
+        match Replay::load(Path::new(REPLAY_FILE)) {
+            Ok(replay) => {
+                self.restore_snapshot(replay.get_start().clone());
+                // Editing would change what gets played back
+                self.editor.enabled = false;
+                self.player = Some(ReplayPlayer::new(replay));
+                self.file_message = None;
+            }
+            Err(err) => self.file_message = Some(err),
+        }
+    }
+
+    fn play_replay_step(&mut self) {
+        let Some(player) = &mut self.player else {
+            return;
+        };
+        let Some((events, delta_time)) = player.next_step() else {
+            let final_positions = self
+                .world
+                .rigid_bodies
+                .iter()
+                .map(|rb| rb.get_pos())
+                .collect::<Vec<_>>();
+            let matches = player.matches_recording(&final_positions);
+            self.file_message = Some(if matches {
+                "The replay ended the same way as the recording".to_owned()
+            } else {
+                "The replay ended differently than the recording".to_owned()
+            });
+            self.player = None;
+            return;
+        };
+        for event in events {
+            self.apply_replay_event(event);
+        }
+        let contact_pairs = self.step(delta_time);
+        self.world.update_sleeping(&contact_pairs, delta_time);
+        self.record_step(delta_time);
+    }
+
+    fn update_camera(&mut self) {
+        if !self.pointer_over_ui {
+            self.camera.update_zoom();
+            if is_mouse_button_pressed(MouseButton::Middle) {
+                self.camera.start_pan();
+            }
+        }
+        if !is_mouse_button_down(MouseButton::Middle) && !is_mouse_button_down(MouseButton::Left) {
+            self.camera.stop_pan();
+        }
+        self.camera.update_pan();
+
+        if self.camera.follow_selected {
+            if let Some(rb) = self.selected.and_then(|handle| self.get_body(handle)) {
+                let center = rb.get_shape().map_or(rb.get_pos(), |shape| {
+                    let (min, max) = shape.get_bounds();
+                    (min + max) * 0.5
+                });
+                self.camera.look_at(center);
+            }
+        }
+    }
+
+    fn update_tools(&mut self) {
+        let mouse_pos = self.camera.get_mouse_pos();
+        let clicked_world = !self.pointer_over_ui;
+
+        if clicked_world && is_mouse_button_pressed(MouseButton::Right) {
+            if let Some(handle) = self.get_body_at(mouse_pos) {
+                self.remove_body(handle);
+            }
+        }
+
+        if self.editor.enabled && matches!(self.toolbar.tool, Tool::Select | Tool::Drag) {
+            self.update_editor(mouse_pos, clicked_world);
+            return;
+        }
+
+        match self.toolbar.tool {
+            Tool::Select => {
+                if clicked_world && is_mouse_button_pressed(MouseButton::Left) {
+                    self.selected = self.get_body_at(mouse_pos);
+                    // Dragging the empty background moves the view
+                    if self.selected.is_none() {
+                        self.camera.start_pan();
+                    }
                 }
-                // v1* = v1 - J * n / m1
-                // v1* / v1 = -j * n / m1
-                //-j =  v1* / v1 / (n / m1) 
-
-                // Coefficient of restitution, bounciness/elasticity. From Newton's Law of Restitution 
-                // https://physics.stackexchange.com/questions/188030/newtons-law-of-restitution
-                // e = relative speed after collision / relative speed before collision, a value of 1 means the objects lose no velocity
-                let mut e = 1.;
-                if let Some(e1) = rb0.get_restitution() {
-                    e = e1;
-                    if let Some(e2) = rb1.get_restitution() {
-                        e = f32::min(e1, e2);
+                return;
+            }
+            Tool::Drag => {
+                self.update_mouse_joint(mouse_pos, clicked_world);
+                return;
+            }
+            Tool::Circle | Tool::Rectangle | Tool::Polygon => {}
+        }
+        if clicked_world && is_mouse_button_pressed(MouseButton::Left) {
+            self.toolbar.start_drag(mouse_pos);
+        }
+        if is_mouse_button_released(MouseButton::Left) {
+            if let Some(body) = self.toolbar.finish_drag(mouse_pos) {
+                self.selected = Some(self.add_body(body));
+            }
+        }
+    }
+
+    // Moves, resizes and rotates the selected body with the gizmo
+    fn update_editor(&mut self, mouse_pos: Vec2, clicked_world: bool) {
+        if clicked_world && is_mouse_button_pressed(MouseButton::Left) {
+            // The handles of the selected body are on top of the other bodies
+            let grabbed = self
+                .selected
+                .and_then(|handle| self.world.rigid_bodies.get(handle))
+                .is_some_and(|rb| self.editor.start_drag(rb, mouse_pos, &self.camera));
+            if !grabbed {
+                self.selected = self.get_body_at(mouse_pos);
+                match self
+                    .selected
+                    .and_then(|handle| self.world.rigid_bodies.get(handle))
+                {
+                    Some(rb) => {
+                        self.editor.start_drag(rb, mouse_pos, &self.camera);
                     }
+                    // Dragging the empty background moves the view
+                    None => self.camera.start_pan(),
                 }
+            }
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.editor.stop_drag();
+            return;
+        }
+        if let Some(rb) = self
+            .selected
+            .and_then(|handle| self.world.rigid_bodies.get_mut(handle))
+        {
+            self.editor.update_drag(rb.as_mut(), mouse_pos);
+        }
+    }
 
-                let inverse_mass_0 = 1. / rb0.get_mass();
-                let inverse_mass_1 = 1. / rb1.get_mass();
+    // The scene with the bodies and variables the way they are now
+    fn get_edited_scene(&self, file_name: &str) -> Scene {
+        // Disabled bodies aren't shown, so they aren't saved either
+        let saved: Vec<(BodyHandle, &Box<dyn RigidBody>)> = self
+            .world
+            .rigid_bodies
+            .iter_with_handles()
+            .filter(|(_, rb)| rb.get_enabled())
+            .collect();
+        let bodies = saved
+            .iter()
+            .map(|(_, rb)| BodyDescription::from_body(rb.as_ref()))
+            .collect();
 
-                // Calculate impulse scalar
-                let mut impulse_scalar = -(1. + e) * vel_along_normal;
-                impulse_scalar /= inverse_mass_0 + inverse_mass_1;
-                //let impulse_scalar =  -(1. + e) * vel_along_normal * (rb0.get_mass() + rb1.get_mass());
+        // The solver knows bodies by their slot, the scene by their place in the list
+        let get_position =
+            |slot: usize| saved.iter().position(|(handle, _)| handle.index() == slot);
+        let ignored_pairs = self
+            .world
+            .solver
+            .get_ignored_pairs()
+            .into_iter()
+            .filter_map(|(a, b)| Some((get_position(a)?, get_position(b)?)))
+            .collect();
+        self.scene
+            .with_changes(file_name, bodies, ignored_pairs, &self.world.vars)
+    }
 
-                // Calculate impulse, clamp the impulse so the simulation won't explode because of extreme velocities
-                let impulse = (impulse_scalar * normal).clamp_length_max(10000.);
+    fn update_mouse_joint(&mut self, mouse_pos: Vec2, clicked_world: bool) {
+        if clicked_world && is_mouse_button_pressed(MouseButton::Left) {
+            self.world.mouse_joint = self.get_body_at(mouse_pos).map(|handle| {
+                MouseJoint::new(
+                    handle,
+                    self.world.rigid_bodies.get(handle).unwrap(),
+                    mouse_pos,
+                    self.toolbar.joint_frequency,
+                    self.toolbar.joint_damping_ratio,
+                )
+            });
+        }
+        if !is_mouse_button_down(MouseButton::Left) {
+            self.world.mouse_joint = None;
+        }
+        if let Some(joint) = &mut self.world.mouse_joint {
+            joint.target = mouse_pos;
+        }
+    }
 
-                // Calculate new velocity based on impulse
-                let new_vel_0 = rb0.get_vel() - inverse_mass_0 * impulse;
-                let new_vel_1 = rb1.get_vel() + inverse_mass_1 * impulse;
-                //let new_pos_0 = rb0.get_pos() + normal * rb0.get_pos().distance(rb1.get_pos());
-                //let new_pos_1 = rb1.get_pos() - normal * rb0.get_pos().distance(rb1.get_pos());
+    // Returns the indices of every two bodies that are touching
+    fn step(&mut self, delta_time: f32) -> Vec<(usize, usize)> {
+        let (contact_pairs, report) = self.world.step(delta_time);
+        self.handle_events();
+        self.step_report = report;
+        if report.speed_limited_bodies > 0 {
+            self.limited_steps += 1;
+        }
+        contact_pairs
+    }
 
-                // Set new velocities
-                self.rigid_bodies[j].set_vel(new_vel_0);
-                self.rigid_bodies[i].set_vel(new_vel_1);
-                
-                //self.rigid_bodies[j].set_pos(new_pos_0);
-                //self.rigid_bodies[i].set_pos(new_pos_1);
+    fn handle_events(&mut self) {
+        for &event in self.world.solver.get_events() {
+            match event {
+                CollisionEvent::TriggerEnter { sensor, .. } => {
+                    *self.sensor_enter_counts.entry(sensor).or_default() += 1;
+                }
+                CollisionEvent::TriggerExit { .. } => {}
+                // Contacts start and end all the time, so only log them when asked to
+                CollisionEvent::BeginContact { .. } | CollisionEvent::EndContact { .. } => {
+                    if !self.log_contacts {
+                        continue;
+                    }
+                }
             }
+            self.event_log
+                .push_back(format!("{}: {event}", self.time_passed.format(2)));
+        }
+        while self.event_log.len() > MAX_LOGGED_EVENTS {
+            self.event_log.pop_front();
+        }
+    }
+    pub fn draw(&self) {
+        let camera = &self.camera;
+        if self.show_field_arrows {
+            self.scene.draw_background(camera, &self.world.vars.fields);
+        } else {
+            self.scene.draw_background(camera, &[]);
+        }
+
+        self.trails.draw(camera);
+
+        // Draw rigidbodies
+        self.world.rigid_bodies.iter().for_each(|rb| {
+            if rb.get_enabled() {
+                rb.draw(camera);
+            }
+        });
+
+        if let Some(sph) = &self.world.sph {
+            sph.draw(camera);
+        }
+        self.debug_draw.draw(
+            camera,
+            &self.world.rigid_bodies,
+            &self.world.solver,
+            self.world.sph.as_ref(),
+        );
+        if self.prediction.enabled && self.player.is_none() {
+            if let Some(path) = self.prediction.get_path() {
+                draw_predicted_path(path, camera);
+            }
+        }
+
+        if let Some(shape) = self
+            .selected
+            .and_then(|handle| self.get_body(handle))
+            .and_then(|rb| rb.get_shape())
+        {
+            let (min, max) = shape.get_bounds();
+            let top_left = camera.world_to_screen(vec2(min.x, max.y));
+            draw_rectangle_lines(
+                top_left.x,
+                top_left.y,
+                camera.metres_to_pixels(max.x - min.x),
+                camera.metres_to_pixels(max.y - min.y),
+                2.,
+                YELLOW,
+            );
+        }
+        if self.editor.enabled {
+            if let Some(rb) = self.selected.and_then(|handle| self.get_body(handle)) {
+                self.editor.draw_gizmo(rb, camera);
+            }
+        }
+        if let Some(joint) = self.world.mouse_joint {
+            if let Some(rb) = self.get_body(joint.body) {
+                joint.draw(rb, camera);
+            }
+        }
+        self.toolbar.draw_preview(camera);
+
+        // Draw the fluid over the bodies so the submerged parts look like they are underwater
+        if let Some(fluid) = self.world.vars.fluid {
+            fluid.draw(camera);
         }
     }
 
@@ -133,42 +736,240 @@ impl Engine {
                 ui.set_max_width(190.);
 
                 ui.heading("General");
-                if ui.button("Next scene").clicked() {
-                    *self = Engine::new(self.scene.get_next_scene());
-                }
+                // Anything that replaces the whole state would break a recording or a replay
+                let replaying =
+                    self.recorder.is_some() || self.player.is_some() || self.trajectory.is_some();
+                ui.add_enabled_ui(!replaying, |ui| {
+                    if let Some(scene) = self.scene_list.update_ui(ui, &self.scene) {
+                        self.start_scene(scene);
+                    }
+                });
 
                 ui.label(format!("FPS: {}", get_fps()));
                 ui.label(format!("time passed: {}", self.time_passed.format(2)));
-                ui.label(format!("World size: {} m", self.world_size));
+                ui.label(format!("World size: {} m", self.world.world_size));
+                self.energy.update_ui(ui);
+                ui.horizontal(|ui| {
+                    let sleeping_count = self
+                        .world
+                        .rigid_bodies
+                        .iter()
+                        .filter(|rb| rb.get_enabled() && rb.get_sleeping())
+                        .count();
+                    ui.label(format!("Sleeping bodies: {sleeping_count}"));
+                    ui.checkbox(&mut self.world.sleeping_enabled, "allow sleeping");
+                });
+                ui.add_enabled_ui(!replaying, |ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button("Reset scene").clicked() {
+                            self.start_scene(self.scene.clone());
+                        }
+                        if ui.button("Reset all entities").clicked() {
+                            self.load_bodies();
+                        }
+                    });
+                });
                 ui.horizontal(|ui| {
-                    if ui.button("Reset scene").clicked() {
-                        *self = Engine::new(self.scene);
+                    let path = Path::new(SNAPSHOT_FILE);
+                    if ui
+                        .button("Save state")
+                        .on_hover_text(format!("Save everything to {SNAPSHOT_FILE}"))
+                        .clicked()
+                    {
+                        let result = self.get_snapshot().save(path);
+                        self.file_message = Some(
+                            result.map_or_else(|err| err, |_| format!("Saved to {SNAPSHOT_FILE}")),
+                        );
                     }
-                    if ui.button("Reset all entities").clicked() {
-                        self.rigid_bodies = self.scene.get_rigid_bodies();
+                    if ui
+                        .add_enabled(!replaying, egui::Button::new("Load state"))
+                        .on_hover_text(format!("Continue from the state in {SNAPSHOT_FILE}"))
+                        .clicked()
+                    {
+                        self.file_message = Some(match Snapshot::load(path) {
+                            Ok(snapshot) => {
+                                self.restore_snapshot(snapshot);
+                                format!("Loaded {SNAPSHOT_FILE}")
+                            }
+                            Err(err) => err,
+                        });
                     }
                 });
+                self.update_replay_ui(ui);
+                self.update_trajectory_ui(ui);
+                if let Some(message) = &self.file_message {
+                    ui.label(message);
+                }
                 ui.horizontal(|ui| {
                     ui.checkbox(&mut self.show_entity_ui, "show entity ui");
+                    ui.checkbox(&mut self.plot.open, "show plot");
                     if !self.time_step_mode_enabled {
                         ui.checkbox(&mut self.pause, "pause");
                     }
                 });
 
+                // Only the replay may change the simulation while it's playing
+                let playing = self.player.is_some();
+                ui.separator();
+                ui.add_enabled_ui(!playing, |ui| {
+                    self.update_tools_ui(ui);
+                    ui.separator();
+                    self.update_editor_ui(ui);
+                });
                 ui.separator();
 
-                self.update_time(ui);
-                self.vars.update_ui(ui, self.scene);
+                self.camera.update_ui(ui);
+                self.debug_draw.update_ui(ui);
+                ui.collapsing("Trails and prediction", |ui| {
+                    self.trails.update_ui(ui);
+                    self.prediction.update_ui(ui);
+                });
+                ui.add_enabled_ui(!playing, |ui| {
+                    self.update_time(ui);
+                    self.update_diagnostics(ui);
+                    self.world.vars.update_ui(ui, &self.scene);
+                    self.update_force_fields(ui);
+                    self.update_bodies_ui(ui);
+                    self.world.solver.update_ui(ui, &self.world.rigid_bodies);
+                    self.update_events_ui(ui);
+                    if let Some(sph) = &mut self.world.sph {
+                        sph.update_ui(ui);
+                    }
+                });
             });
 
-            if self.show_entity_ui {
-                for i in 0..self.rigid_bodies.len() {
-                    self.rigid_bodies[i].update_based_on_ui(egui_ctx, i + 1);
+            if self.plot.open {
+                self.plot
+                    .update_ui(egui_ctx, &self.world.rigid_bodies, self.selected);
+            }
+
+            if self.player.is_some() {
+                // The entity windows can change bodies, so they stay closed during a replay
+            } else if self.show_entity_ui {
+                for (handle, rb) in self.world.rigid_bodies.iter_mut_with_handles() {
+                    rb.update_based_on_ui(egui_ctx, handle);
+                }
+            } else if self.editor.enabled {
+                // The window of the selected body works as the property inspector
+                if let Some(handle) = self.selected {
+                    if let Some(rb) = self.world.rigid_bodies.get_mut(handle) {
+                        rb.update_based_on_ui(egui_ctx, handle);
+                    }
+                }
+            }
+            self.pointer_over_ui =
+                egui_ctx.is_pointer_over_area() || egui_ctx.wants_pointer_input();
+        });
+    }
+
+    fn update_replay_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            if let Some(recorder) = &self.recorder {
+                ui.label(format!("Recording: {} steps", recorder.get_step_count()));
+                if ui.button("Stop").clicked() {
+                    self.stop_recording();
+                }
+            } else if let Some(player) = &self.player {
+                let (played, total) = player.get_progress();
+                ui.label(format!("Replaying: {played}/{total}"));
+                if ui.button("Stop").clicked() {
+                    self.player = None;
+                }
+            } else {
+                if ui
+                    .button("Record")
+                    .on_hover_text(format!(
+                        "Record every step and change from now on, saved to {REPLAY_FILE} when stopped"
+                    ))
+                    .clicked()
+                {
+                    self.start_recording();
+                }
+                if ui
+                    .button("Play replay")
+                    .on_hover_text(format!("Run the recording in {REPLAY_FILE} again"))
+                    .clicked()
+                {
+                    self.start_replay();
                 }
             }
         });
     }
 
+    fn update_trajectory_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            if let Some(trajectory) = &self.trajectory {
+                ui.label(format!(
+                    "Trajectory: {} samples",
+                    trajectory.get_sample_count()
+                ));
+                if ui
+                    .button("Export")
+                    .on_hover_text(format!("Stop recording and save to {TRAJECTORY_CSV_FILE}"))
+                    .clicked()
+                {
+                    self.stop_trajectory();
+                }
+            } else {
+                if ui
+                    .button("Record trajectory")
+                    .on_hover_text(
+                        "Keep the position, velocity, kinetic energy and forces of every body",
+                    )
+                    .clicked()
+                {
+                    self.start_trajectory(self.trajectory_interval);
+                }
+                ui.checkbox(&mut self.trajectory_binary, "binary")
+                    .on_hover_text(format!("Also export to {TRAJECTORY_BINARY_FILE}"));
+            }
+        });
+        if self.trajectory.is_none() {
+            ui.horizontal(|ui| {
+                ui.label("Sample every");
+                ui.add(egui::DragValue::new(&mut self.trajectory_interval).clamp_range(1..=1000));
+                ui.label("steps");
+            });
+        }
+    }
+
+    fn update_tools_ui(&mut self, ui: &mut Ui) {
+        ui.heading("Tools");
+        self.toolbar.update_ui(ui);
+
+        let Some(handle) = self
+            .selected
+            .filter(|&handle| self.get_body(handle).is_some())
+        else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label(format!("Selected: {handle}"));
+            if ui.button("Duplicate").clicked() {
+                self.duplicate_selected();
+            }
+            if ui.button("Remove").clicked() {
+                self.remove_body(handle);
+            }
+        });
+    }
+
+    fn update_editor_ui(&mut self, ui: &mut Ui) {
+        let Some(file_name) = self.editor.update_ui(ui) else {
+            return;
+        };
+        let scene = self.get_edited_scene(&file_name);
+        self.editor.message = Some(match scene.save() {
+            Ok(path) => {
+                // Resetting the scene goes back to what was saved from now on
+                self.scene = scene;
+                self.scene_list.reload();
+                format!("Saved to {}", path.display())
+            }
+            Err(err) => err,
+        });
+    }
+
     fn update_time(&mut self, ui: &mut Ui) {
         ui.collapsing("Show time settings", |ui| {
             ui.checkbox(&mut self.time_step_mode_enabled, "time step mode enabled");
@@ -192,34 +993,174 @@ impl Engine {
                     }
                 });
             }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("Velocity iterations:")
+                    .on_hover_text("How many times all contacts are solved each step");
+                ui.add(egui::Slider::new(
+                    &mut self.world.solver.velocity_iterations,
+                    1..=50,
+                ));
+            });
+            ui.checkbox(&mut self.world.solver.warm_starting, "warm starting")
+                .on_hover_text("Start solving contacts from the impulses of the last step");
+
+            let mut limit_speed = self.world.speed_limit.is_some();
+            ui.checkbox(&mut limit_speed, "limit speed").on_hover_text(
+                "Slow down bodies that move faster than this. Keeps extreme scenes stable, but removes momentum",
+            );
+            if !limit_speed {
+                self.world.speed_limit = None;
+            } else {
+                let mut speed_limit = self.world.speed_limit.unwrap_or(DEFAULT_SPEED_LIMIT);
+                ui.horizontal(|ui| {
+                    ui.label("Speed limit:");
+                    ui.add(egui::Slider::new(&mut speed_limit, (1.)..=1000.));
+                    ui.label("m/s");
+                });
+                self.world.speed_limit = Some(speed_limit);
+            }
         });
         if !self.time_step_mode_enabled {
             self.time_passed += get_frame_time();
         }
     }
 
+    fn update_diagnostics(&mut self, ui: &mut Ui) {
+        ui.collapsing("Show diagnostics", |ui| {
+            let report = self.step_report;
+            ui.label(format!(
+                "Momentum error: {} kg*m/s",
+                report.contact_momentum_error.format(3)
+            ))
+            .on_hover_text(
+                "Change in total momentum during the last step from contacts between bodies, this should be about 0",
+            );
+            if report.speed_limited_bodies > 0 {
+                ui.colored_label(
+                    egui::Color32::RED,
+                    format!(
+                        "Speed limit hit by {} bodies, removed {} kg*m/s",
+                        report.speed_limited_bodies,
+                        report.speed_limit_momentum.format(2)
+                    ),
+                );
+            }
+            ui.label(format!("Steps with a limit hit: {}", self.limited_steps));
+        });
+    }
+
+    fn update_bodies_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Show bodies", |ui| {
+            let mut removed = None;
+            for (handle, rb) in self.world.rigid_bodies.iter_with_handles() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{:?} {handle}", rb.get_type()));
+                    if ui.button("Remove").clicked() {
+                        removed = Some(handle);
+                    }
+                });
+            }
+            if let Some(handle) = removed {
+                self.remove_body(handle);
+            }
+        });
+    }
+
+    fn update_events_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Show collision events", |ui| {
+            for (&sensor, count) in self.sensor_enter_counts.iter() {
+                // It stops counting when it isn't a sensor anymore
+                if self.get_body(sensor).is_some_and(|rb| rb.get_sensor()) {
+                    ui.label(format!("Sensor {sensor}: {count} entered"));
+                }
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.log_contacts, "log contacts")
+                    .on_hover_text("Also log bodies starting and stopping to touch");
+                if ui.button("Clear").clicked() {
+                    self.event_log.clear();
+                }
+            });
+            egui::ScrollArea::vertical()
+                .max_height(150.)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in self.event_log.iter() {
+                        ui.label(line);
+                    }
+                });
+        });
+    }
+
+    fn update_force_fields(&mut self, ui: &mut Ui) {
+        if self.world.vars.fields.is_empty() {
+            return;
+        }
+        ui.collapsing("Show force fields", |ui| {
+            ui.checkbox(&mut self.show_field_arrows, "draw field arrows");
+            for field in self.world.vars.fields.iter_mut() {
+                ui.separator();
+                field.update_ui(ui, self.world.world_size);
+            }
+        });
+    }
+
     fn create_time_step_button(&mut self, ui: &mut Ui, title: &str, increment: f32) {
         if ui.button(title).clicked() {
             self.time_passed += increment;
-            self.rigid_bodies.iter_mut().for_each(|rb| {
-                if rb.get_enabled() {
-                    rb.apply_forces(self.vars, increment, self.scene.get_world_size());
-                }
-            });
+            self.advance(increment);
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+// Where a body would go during the next `duration` seconds, simulated on a copy of the world.
+// That's `preview` if a body is being spawned, or else the `followed` one. A body held by the
+// mouse is predicted as if it was let go
+fn predict_path(
+    world: &World,
+    preview: Option<Box<dyn RigidBody>>,
+    followed: Option<BodyHandle>,
+    duration: f32,
+) -> Option<Vec<Vec2>> {
+    let mut world = world.without_fluid();
+    world.mouse_joint = None;
+    let handle = match preview {
+        Some(body) => world.rigid_bodies.insert(body),
+        None => followed?,
+    };
+
+    let steps = (duration / PREDICTION_STEP) as usize;
+    let mut path = Vec::with_capacity(steps + 1);
+    path.push(world.rigid_bodies.get(handle)?.get_center_of_mass());
+    for _ in 0..steps {
+        let (contact_pairs, _) = world.step(PREDICTION_STEP);
+        world.update_sleeping(&contact_pairs, PREDICTION_STEP);
+        path.push(world.rigid_bodies.get(handle)?.get_center_of_mass());
+    }
+    Some(path)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variables {
     pub g: Option<f32>,
-    pub c: Option<f32>,
+    pub rho: Option<f32>,
+    pub mu: Option<f32>,
+    pub fluid: Option<FluidVolume>,
+    pub fields: Vec<ForceField>,
 }
 impl Variables {
-    pub fn new(g: Option<f32>, c: Option<f32>) -> Self {
-        Variables { g, c }
+    pub fn new(g: Option<f32>, rho: Option<f32>, mu: Option<f32>) -> Self {
+        Variables {
+            g,
+            rho,
+            mu,
+            fluid: None,
+            fields: Vec::new(),
+        }
     }
-    pub fn update_ui(&mut self, ui: &mut Ui, scene: Scene) {
+    pub fn update_ui(&mut self, ui: &mut Ui, scene: &Scene) {
         ui.collapsing("Show variables", |ui| {
             if let Some(mut g) = self.g {
                 ui.horizontal(|ui| {
@@ -230,15 +1171,56 @@ impl Variables {
                 self.g = Some(g);
             }
 
-            if let Some(mut c) = self.c {
+            if let Some(mut rho) = self.rho {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("ρ:")
+                        .on_hover_text("Density of the surrounding fluid in kg/m³");
+                    ui.add(egui::Slider::new(&mut rho, scene.get_rho_range()));
+                });
+                self.create_reset_buttons(ui, &mut rho, scene.get_rho_default());
+                self.rho = Some(rho);
+            }
+
+            if let Some(mut mu) = self.mu {
                 ui.separator();
                 ui.horizontal(|ui| {
-                    ui.label("c:")
-                        .on_hover_text("Multiplier for the air resistance");
-                    ui.add(egui::Slider::new(&mut c, scene.get_c_range()));
+                    ui.label("μ:").on_hover_text(
+                        "Viscosity of the surrounding fluid in Pa*s, used for the linear drag",
+                    );
+                    ui.add(egui::Slider::new(&mut mu, (0.)..=2.));
                 });
-                self.create_reset_buttons(ui, &mut c, scene.get_c_default());
-                self.c = Some(c);
+                self.create_reset_buttons(ui, &mut mu, 0.);
+                self.mu = Some(mu);
+            }
+
+            if let Some(mut fluid) = self.fluid {
+                let default_fluid = scene.get_variables().fluid.unwrap_or(fluid);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("ρ fluid:")
+                        .on_hover_text("Density of the fluid volume in kg/m³");
+                    ui.add(egui::Slider::new(&mut fluid.density, (0.)..=30.));
+                });
+                self.create_reset_buttons(ui, &mut fluid.density, default_fluid.density);
+
+                ui.horizontal(|ui| {
+                    ui.label("μ fluid:")
+                        .on_hover_text("Viscosity of the fluid volume in Pa*s");
+                    ui.add(egui::Slider::new(&mut fluid.viscosity, (0.)..=5.));
+                });
+                self.create_reset_buttons(ui, &mut fluid.viscosity, default_fluid.viscosity);
+
+                ui.horizontal(|ui| {
+                    ui.label("Surface level:");
+                    let bottom = fluid.pos.y - fluid.size.y;
+                    ui.add(egui::Slider::new(
+                        &mut fluid.surface_level,
+                        bottom..=fluid.pos.y,
+                    ));
+                    ui.label("m");
+                });
+                self.fluid = Some(fluid);
             }
         });
     }
@@ -254,3 +1236,90 @@ impl Variables {
         });
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_engine(file_name: &str) -> Engine {
+        let scene_list = SceneList::load();
+        let scene = scene_list.get(file_name).unwrap();
+        Engine::new(scene, scene_list)
+    }
+
+    // Frames don't all take the same time
+    fn get_delta_time(step: usize) -> f32 {
+        if step.is_multiple_of(3) {
+            1. / 50.
+        } else {
+            1. / 60.
+        }
+    }
+
+    #[test]
+    fn replay_ends_where_the_recording_did() {
+        let mut engine = get_engine("bouncing_circles");
+        engine.start_recording();
+        for step in 0..120 {
+            // The user changes things in between steps
+            if step == 30 {
+                engine.world.vars.g = Some(-3.);
+            }
+            if step == 60 {
+                let handle = engine.world.rigid_bodies.get_handle(0).unwrap();
+                engine.remove_body(handle);
+            }
+            if step == 90 {
+                engine.world.speed_limit = Some(1.);
+            }
+            engine.advance(get_delta_time(step));
+        }
+        let replay = engine
+            .recorder
+            .take()
+            .unwrap()
+            .finish(engine.get_final_positions());
+        assert_eq!(replay.get_step_count(), 120);
+
+        // Through a file and back
+        let ron = ron::to_string(&replay).unwrap();
+        let replay: Replay = ron::from_str(&ron).unwrap();
+
+        let mut player = get_engine("bouncing_circles");
+        player.restore_snapshot(replay.get_start().clone());
+        player.player = Some(ReplayPlayer::new(replay));
+        while player.player.is_some() {
+            player.play_replay_step();
+        }
+        assert_eq!(
+            player.file_message.as_deref(),
+            Some("The replay ended the same way as the recording")
+        );
+        assert_eq!(player.get_final_positions(), engine.get_final_positions());
+    }
+
+    #[test]
+    fn only_what_the_user_changed_is_recorded() {
+        let mut engine = get_engine("falling_rectangles");
+        engine.start_recording();
+        for step in 0..10 {
+            if step == 5 {
+                engine.world.vars.g = Some(-3.);
+            }
+            engine.advance(get_delta_time(step));
+        }
+        let replay = engine.recorder.take().unwrap().finish(Vec::new());
+        let mut player = ReplayPlayer::new(replay);
+        for step in 0..10 {
+            let (events, delta_time) = player.next_step().unwrap();
+            assert_eq!(delta_time, get_delta_time(step));
+            match step {
+                5 => assert!(
+                    matches!(events[..], [ReplayEvent::Variables(ref vars)] if vars.g == Some(-3.))
+                ),
+                _ => assert!(events.is_empty(), "{} events at step {step}", events.len()),
+            }
+        }
+        assert!(player.next_step().is_none());
+    }
+}
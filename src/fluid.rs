@@ -0,0 +1,220 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    rigid_body::{get_drag, BODY_DEPTH},
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FluidVolume {
+    // Top left corner of the container, the same way rectangles are positioned
+    pub pos: Vec2,
+    pub size: Vec2,
+    // Height of the surface of the fluid in world coordinates
+    pub surface_level: f32,
+    pub density: f32,
+    pub viscosity: f32,
+}
+impl FluidVolume {
+    // Returns the buoyancy and the drag of the fluid on a body of which `submerged_area` out of `area` is submerged
+    pub fn calculate_forces(
+        &self,
+        g: Option<f32>,
+        submerged_area: f32,
+        area: f32,
+        vel: Vec2,
+        drag_coefficient: f32,
+        width: f32,
+    ) -> (f32, Vec2) {
+        if submerged_area <= 0. {
+            return (0., Vec2::ZERO);
+        }
+
+        // Archimedes: F_b = rho * V * g, pointing up
+        let f_buoyancy = self.density * submerged_area * BODY_DEPTH * g.unwrap_or(0.);
+
+        // Only the submerged part of the body feels the drag of the fluid
+        let submerged_fraction = submerged_area / area;
+        let f_drag = get_drag(
+            self.density * submerged_fraction,
+            self.viscosity * submerged_fraction,
+            vel,
+            drag_coefficient,
+            width,
+        );
+
+        (f_buoyancy, f_drag)
+    }
+
+    // Area of the circle that is below the surface and above the bottom of the container.
+    // The circle counts as outside of the container if its centre is.
+    pub fn get_submerged_area_circle(&self, center: Vec2, radius: f32) -> f32 {
+        if center.x < self.pos.x || center.x > self.pos.x + self.size.x {
+            return 0.;
+        }
+        let bottom = self.pos.y - self.size.y;
+        let area_below_surface = get_circle_area_below(center, radius, self.surface_level);
+        let area_below_bottom = get_circle_area_below(center, radius, bottom);
+        area_below_surface - area_below_bottom
+    }
+
+    // Area of the rectangle at `pos` (top left corner) that is inside the fluid
+    pub fn get_submerged_area_rect(&self, pos: Vec2, size: Vec2) -> f32 {
+        let overlap_x = f32::min(pos.x + size.x, self.pos.x + self.size.x) - pos.x.max(self.pos.x);
+        let bottom = self.pos.y - self.size.y;
+        let overlap_y = f32::min(pos.y, self.surface_level) - f32::max(pos.y - size.y, bottom);
+        overlap_x.max(0.) * overlap_y.max(0.)
+    }
+
+    // Area of the polygon with the corners `vertices` that is inside the fluid
+    pub fn get_submerged_area_polygon(&self, vertices: &[Vec2]) -> f32 {
+        let bottom = self.pos.y - self.size.y;
+        // Cut off everything outside of the fluid, one side at a time
+        let mut inside = vertices.to_vec();
+        inside = clip_polygon(&inside, vec2(-1., 0.), -self.pos.x);
+        inside = clip_polygon(&inside, vec2(1., 0.), self.pos.x + self.size.x);
+        inside = clip_polygon(&inside, vec2(0., -1.), -bottom);
+        inside = clip_polygon(&inside, vec2(0., 1.), self.surface_level);
+        get_polygon_area(&inside)
+    }
+
+    pub fn draw(&self, camera: &Camera) {
+        let bottom = self.pos.y - self.size.y;
+        let surface = camera.world_to_screen(vec2(self.pos.x, self.surface_level));
+        draw_rectangle(
+            surface.x,
+            surface.y,
+            camera.metres_to_pixels(self.size.x),
+            camera.metres_to_pixels(self.surface_level - bottom),
+            Color::new(0., 0.47, 0.95, 0.4),
+        );
+        let pos = camera.world_to_screen(self.pos);
+        draw_rectangle_lines(
+            pos.x,
+            pos.y,
+            camera.metres_to_pixels(self.size.x),
+            camera.metres_to_pixels(self.size.y),
+            3.,
+            DARKBLUE,
+        );
+    }
+}
+
+// Area of the circular segment below the horizontal line at height `level`
+fn get_circle_area_below(center: Vec2, radius: f32, level: f32) -> f32 {
+    // Signed distance from the centre to the line
+    let d = (level - center.y).clamp(-radius, radius);
+    radius * radius * (-d / radius).acos() + d * (radius * radius - d * d).sqrt()
+}
+
+// The part of the polygon where `v.dot(normal) <= limit`
+fn clip_polygon(vertices: &[Vec2], normal: Vec2, limit: f32) -> Vec<Vec2> {
+    let mut clipped = Vec::new();
+    for (i, &start) in vertices.iter().enumerate() {
+        let end = vertices[(i + 1) % vertices.len()];
+        let start_dist = start.dot(normal) - limit;
+        let end_dist = end.dot(normal) - limit;
+        if start_dist <= 0. {
+            clipped.push(start);
+        }
+        // The edge crosses the line, so it gets cut where it does
+        if (start_dist <= 0.) != (end_dist <= 0.) {
+            clipped.push(start.lerp(end, start_dist / (start_dist - end_dist)));
+        }
+    }
+    clipped
+}
+
+// Shoelace formula, positive for vertices going counter clockwise
+pub fn get_polygon_area(vertices: &[Vec2]) -> f32 {
+    let mut double_area = 0.;
+    for (i, &v) in vertices.iter().enumerate() {
+        double_area += v.perp_dot(vertices[(i + 1) % vertices.len()]);
+    }
+    double_area * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    // Container from (0, 0) to (10, 10), filled up to half its height
+    fn get_fluid() -> FluidVolume {
+        FluidVolume {
+            pos: vec2(0., 10.),
+            size: vec2(10., 10.),
+            surface_level: 5.,
+            density: 1.,
+            viscosity: 0.,
+        }
+    }
+
+    #[test]
+    fn circle_is_submerged_by_how_far_it_is_below_the_surface() {
+        let fluid = get_fluid();
+        let full = PI * 0.5 * 0.5;
+        assert!((fluid.get_submerged_area_circle(vec2(5., 2.), 0.5) - full).abs() < 1e-4);
+        assert!((fluid.get_submerged_area_circle(vec2(5., 5.), 0.5) - full * 0.5).abs() < 1e-4);
+        assert_eq!(fluid.get_submerged_area_circle(vec2(5., 8.), 0.5), 0.);
+    }
+
+    #[test]
+    fn circle_outside_of_the_container_isnt_submerged() {
+        let fluid = get_fluid();
+        assert_eq!(fluid.get_submerged_area_circle(vec2(-1., 2.), 0.5), 0.);
+        assert_eq!(fluid.get_submerged_area_circle(vec2(11., 2.), 0.5), 0.);
+        // Below the bottom of the container
+        assert!(fluid.get_submerged_area_circle(vec2(5., -2.), 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn rectangle_is_cut_off_at_the_surface_and_the_walls() {
+        let fluid = get_fluid();
+        assert_eq!(
+            fluid.get_submerged_area_rect(vec2(4., 4.), vec2(2., 2.)),
+            4.
+        );
+        assert_eq!(
+            fluid.get_submerged_area_rect(vec2(4., 6.), vec2(2., 2.)),
+            2.
+        );
+        assert_eq!(
+            fluid.get_submerged_area_rect(vec2(9., 4.), vec2(2., 2.)),
+            2.
+        );
+        assert_eq!(
+            fluid.get_submerged_area_rect(vec2(4., 8.), vec2(2., 2.)),
+            0.
+        );
+    }
+
+    #[test]
+    fn no_forces_without_anything_submerged() {
+        let fluid = get_fluid();
+        let (f_buoyancy, f_drag) = fluid.calculate_forces(Some(9.81), 0., 1., vec2(1., 0.), 1., 1.);
+        assert_eq!(f_buoyancy, 0.);
+        assert_eq!(f_drag, Vec2::ZERO);
+    }
+
+    #[test]
+    fn polygon_is_cut_off_at_the_surface_and_the_walls() {
+        let fluid = get_fluid();
+        let square = [vec2(4., 4.), vec2(6., 4.), vec2(6., 6.), vec2(4., 6.)];
+        assert!((fluid.get_submerged_area_polygon(&square) - 2.).abs() < 1e-4);
+        // A triangle with its right half outside the container
+        let triangle = [vec2(9., 0.), vec2(11., 0.), vec2(10., 2.)];
+        assert!((fluid.get_submerged_area_polygon(&triangle) - 1.).abs() < 1e-4);
+        let above = [vec2(4., 6.), vec2(6., 6.), vec2(5., 8.)];
+        assert_eq!(fluid.get_submerged_area_polygon(&above), 0.);
+    }
+
+    #[test]
+    fn polygon_area_is_negative_going_clockwise() {
+        let square = [vec2(0., 0.), vec2(2., 0.), vec2(2., 2.), vec2(0., 2.)];
+        assert_eq!(get_polygon_area(&square), 4.);
+        let reversed: Vec<Vec2> = square.iter().rev().copied().collect();
+        assert_eq!(get_polygon_area(&reversed), -4.);
+    }
+}
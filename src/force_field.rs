@@ -0,0 +1,251 @@
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+
+// Closest distance to the centre of a radial field or vortex that is used for the falloff,
+// so bodies don't get flung away when they get too close
+const MIN_FIELD_DIST: f32 = 1.;
+
+// A field strength in N/kg defined over the whole world, acting on every body like gravity does
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ForceFieldKind {
+    Wind {
+        strength: Vec2,
+    },
+    // Positive strengths attract, negative strengths repel
+    Radial {
+        center: Vec2,
+        strength: f32,
+    },
+    // Positive strengths spin counterclockwise
+    Vortex {
+        center: Vec2,
+        strength: f32,
+    },
+    // Values are stored row by row starting at the bottom left of the grid
+    Grid {
+        origin: Vec2,
+        cell_size: f32,
+        columns: usize,
+        values: Vec<Vec2>,
+        scale: f32,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForceField {
+    pub enabled: bool,
+    pub kind: ForceFieldKind,
+}
+impl ForceField {
+    pub fn new(kind: ForceFieldKind) -> Self {
+        Self {
+            enabled: false,
+            kind,
+        }
+    }
+
+    // One field of every kind, all disabled, so they can be toggled from the ui
+    pub fn get_defaults(world_size: Vec2) -> Vec<ForceField> {
+        let center = world_size * 0.5;
+
+        // Rising and sinking columns of air, like thermals above warm ground
+        let cell_size = 4.;
+        let columns = (world_size.x / cell_size) as usize + 1;
+        let rows = (world_size.y / cell_size) as usize + 1;
+        let mut values = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let x = column as f32 / columns as f32 * std::f32::consts::TAU * 2.;
+                let y = row as f32 / rows as f32;
+                values.push(vec2(0.2 * x.cos(), x.sin() * (1. - y)) * 10.);
+            }
+        }
+
+        vec![
+            ForceField::new(ForceFieldKind::Wind {
+                strength: vec2(5., 0.),
+            }),
+            ForceField::new(ForceFieldKind::Radial {
+                center,
+                strength: 100.,
+            }),
+            ForceField::new(ForceFieldKind::Vortex {
+                center,
+                strength: 20.,
+            }),
+            ForceField::new(ForceFieldKind::Grid {
+                origin: Vec2::ZERO,
+                cell_size,
+                columns,
+                values,
+                scale: 1.,
+            }),
+        ]
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self.kind {
+            ForceFieldKind::Wind { .. } => "Wind",
+            ForceFieldKind::Radial { .. } => "Radial",
+            ForceFieldKind::Vortex { .. } => "Vortex",
+            ForceFieldKind::Grid { .. } => "Grid",
+        }
+    }
+
+    // Field strength at `pos` in N/kg
+    pub fn sample(&self, pos: Vec2) -> Vec2 {
+        if !self.enabled {
+            return Vec2::ZERO;
+        }
+        match &self.kind {
+            ForceFieldKind::Wind { strength } => *strength,
+            ForceFieldKind::Radial { center, strength } => {
+                // Falls off with the square of the distance, like gravity around a planet
+                let to_center = *center - pos;
+                let dist = to_center.length().max(MIN_FIELD_DIST);
+                to_center.normalize_or_zero() * *strength / (dist * dist)
+            }
+            ForceFieldKind::Vortex { center, strength } => {
+                let to_center = *center - pos;
+                let dist = to_center.length().max(MIN_FIELD_DIST);
+                -to_center.normalize_or_zero().perp() * *strength / dist
+            }
+            ForceFieldKind::Grid {
+                origin,
+                cell_size,
+                columns,
+                values,
+                scale,
+            } => sample_grid(*origin, *cell_size, *columns, values, pos) * *scale,
+        }
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui, world_size: Vec2) {
+        let name = self.get_name();
+        ui.checkbox(&mut self.enabled, name);
+        if !self.enabled {
+            return;
+        }
+        match &mut self.kind {
+            ForceFieldKind::Wind { strength } => {
+                ui.horizontal(|ui| {
+                    ui.label("x:");
+                    ui.add(egui::Slider::new(&mut strength.x, (-30.)..=30.));
+                    ui.label("N/kg");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("y:");
+                    ui.add(egui::Slider::new(&mut strength.y, (-30.)..=30.));
+                    ui.label("N/kg");
+                });
+            }
+            ForceFieldKind::Radial { center, strength }
+            | ForceFieldKind::Vortex { center, strength } => {
+                ui.horizontal(|ui| {
+                    ui.label("Strength:")
+                        .on_hover_text("Field strength at 1 m from the centre in N/kg");
+                    ui.add(egui::Slider::new(strength, (-500.)..=500.));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Centre x:");
+                    ui.add(egui::Slider::new(&mut center.x, (0.)..=world_size.x));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Centre y:");
+                    ui.add(egui::Slider::new(&mut center.y, (0.)..=world_size.y));
+                });
+            }
+            ForceFieldKind::Grid { scale, .. } => {
+                ui.horizontal(|ui| {
+                    ui.label("Scale:")
+                        .on_hover_text("Multiplier for the sampled field strength");
+                    ui.add(egui::Slider::new(scale, (-5.)..=5.));
+                });
+            }
+        }
+    }
+}
+
+// Sum of all enabled fields at `pos` in N/kg, None if none of them are enabled
+pub fn sample_fields(fields: &[ForceField], pos: Vec2) -> Option<Vec2> {
+    if !fields.iter().any(|field| field.enabled) {
+        return None;
+    }
+    Some(
+        fields
+            .iter()
+            .fold(Vec2::ZERO, |sum, field| sum + field.sample(pos)),
+    )
+}
+
+// Force the enabled fields put on a body with `mass` at `pos`. F = m * a, the same way gravity
+// works
+pub fn get_field_force(fields: &[ForceField], pos: Vec2, mass: f32) -> Option<Vec2> {
+    sample_fields(fields, pos).map(|field| field * mass)
+}
+
+// Draws an arrow for the enabled fields on every `spacing` metres of the background grid
+pub fn draw_field_arrows(fields: &[ForceField], world_size: Vec2, spacing: usize, camera: &Camera) {
+    let mut samples = Vec::new();
+    for x in (0..=(world_size.x as usize)).step_by(spacing) {
+        for y in (0..=(world_size.y as usize)).step_by(spacing) {
+            let pos = vec2(x as f32, y as f32);
+            if let Some(field) = sample_fields(fields, pos) {
+                samples.push((pos, field));
+            }
+        }
+    }
+
+    // Scale the arrows so the strongest one is almost as long as the spacing
+    let max_strength = samples
+        .iter()
+        .map(|(_, field)| field.length())
+        .fold(0., f32::max);
+    if max_strength == 0. {
+        return;
+    }
+    let scale = spacing as f32 * 0.8 / max_strength;
+
+    for (pos, field) in samples {
+        let start = camera.world_to_screen(pos);
+        let end = camera.world_to_screen(pos + field * scale);
+        draw_arrow(start, end, 1.5, DARKGREEN);
+    }
+}
+
+// Draws an arrow between two points in pixels
+pub fn draw_arrow(start: Vec2, end: Vec2, thickness: f32, color: Color) {
+    draw_line(start.x, start.y, end.x, end.y, thickness, color);
+
+    let dir = (end - start).normalize_or_zero();
+    let head_size = f32::min(start.distance(end) * 0.4, thickness * 5.);
+    let back = end - dir * head_size;
+    let side = dir.perp() * head_size * 0.5;
+    draw_triangle(end, back + side, back - side, color);
+}
+
+// Bilinear interpolation between the four grid points around `pos`, zero outside of the grid
+fn sample_grid(origin: Vec2, cell_size: f32, columns: usize, values: &[Vec2], pos: Vec2) -> Vec2 {
+    // A grid loaded from a file can be empty
+    if columns == 0 || values.is_empty() {
+        return Vec2::ZERO;
+    }
+    let rows = values.len() / columns;
+    let grid_pos = (pos - origin) / cell_size;
+    if grid_pos.x < 0. || grid_pos.y < 0. {
+        return Vec2::ZERO;
+    }
+    let (column, row) = (grid_pos.x as usize, grid_pos.y as usize);
+    if column + 1 >= columns || row + 1 >= rows {
+        return Vec2::ZERO;
+    }
+    let t = grid_pos - vec2(column as f32, row as f32);
+    let get = |column: usize, row: usize| values[row * columns + column];
+
+    let bottom = get(column, row).lerp(get(column + 1, row), t.x);
+    let top = get(column, row + 1).lerp(get(column + 1, row + 1), t.x);
+    bottom.lerp(top, t.y)
+}
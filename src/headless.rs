@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use crate::{engine::Engine, scenes::SceneList};
+
+pub const USAGE: &str = "\
+Usage: physics_engine --headless [options]
+  --scene NAME            file name of the scene to run, the first one if not given
+  --duration SECONDS      how long to simulate, 10 by default
+  --dt SECONDS            length of every step, 1/60 by default
+  --sample-interval STEPS keep every this many steps of the trajectory, 1 by default
+  --csv PATH              export the trajectory as CSV
+  --binary PATH           export the trajectory in the binary format";
+
+// Settings for running a scene without a window
+#[derive(Debug, Clone)]
+struct Options {
+    scene: Option<String>,
+    duration: f32,
+    delta_time: f32,
+    sample_interval: u32,
+    csv: Option<PathBuf>,
+    binary: Option<PathBuf>,
+}
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            scene: None,
+            duration: 10.,
+            delta_time: 1. / 60.,
+            sample_interval: 1,
+            csv: None,
+            binary: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("{arg} needs a value\n{USAGE}"))
+            };
+            let invalid = |value: &String| format!("Invalid value for {arg}: {value}");
+            match arg.as_str() {
+                "--scene" => options.scene = Some(value()?.clone()),
+                "--duration" => {
+                    let value = value()?;
+                    options.duration = value.parse().map_err(|_| invalid(value))?;
+                }
+                "--dt" => {
+                    let value = value()?;
+                    options.delta_time = value
+                        .parse()
+                        .ok()
+                        .filter(|&dt: &f32| dt > 0.)
+                        .ok_or_else(|| invalid(value))?;
+                }
+                "--sample-interval" => {
+                    let value = value()?;
+                    options.sample_interval = value.parse().map_err(|_| invalid(value))?;
+                }
+                "--csv" => options.csv = Some(value()?.into()),
+                "--binary" => options.binary = Some(value()?.into()),
+                _ => return Err(format!("Unknown option {arg}\n{USAGE}")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Runs a scene as fast as possible and exports what happened, for scripts and notebooks
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args)?;
+    let scene_list = SceneList::load();
+    let scene = match &options.scene {
+        Some(name) => scene_list
+            .get(name)
+            .ok_or_else(|| format!("There is no scene called {name}"))?,
+        None => scene_list.get_first(),
+    };
+    let mut engine = Engine::new(scene, scene_list);
+
+    let exporting = options.csv.is_some() || options.binary.is_some();
+    if exporting {
+        engine.start_trajectory(options.sample_interval);
+    }
+    let steps = (options.duration / options.delta_time).round() as u32;
+    for _ in 0..steps {
+        engine.advance(options.delta_time);
+    }
+    println!("Simulated {steps} steps");
+
+    let Some(trajectory) = engine.take_trajectory() else {
+        return Ok(());
+    };
+    if let Some(path) = &options.csv {
+        trajectory.save_csv(path)?;
+        println!(
+            "Exported {} samples to {}",
+            trajectory.get_sample_count(),
+            path.display()
+        );
+    }
+    if let Some(path) = &options.binary {
+        trajectory.save_binary(path)?;
+        println!(
+            "Exported {} samples to {}",
+            trajectory.get_sample_count(),
+            path.display()
+        );
+    }
+    Ok(())
+}
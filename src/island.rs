@@ -0,0 +1,62 @@
+// Groups bodies that touch each other, directly or through other bodies, into islands.
+// `pairs` holds the indices of every two bodies that are in contact.
+pub fn find_islands(body_count: usize, pairs: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    // Union-find, every body starts out as its own island
+    let mut parents: Vec<usize> = (0..body_count).collect();
+
+    for &(a, b) in pairs {
+        let root_a = find_root(&mut parents, a);
+        let root_b = find_root(&mut parents, b);
+        if root_a != root_b {
+            parents[root_b] = root_a;
+        }
+    }
+
+    let mut islands: Vec<Vec<usize>> = vec![Vec::new(); body_count];
+    for i in 0..body_count {
+        let root = find_root(&mut parents, i);
+        islands[root].push(i);
+    }
+    islands.retain(|island| !island.is_empty());
+    islands
+}
+
+fn find_root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        // Point halfway up the tree so later lookups are faster
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut islands: Vec<Vec<usize>>) -> Vec<Vec<usize>> {
+        islands.iter_mut().for_each(|island| island.sort_unstable());
+        islands.sort();
+        islands
+    }
+
+    #[test]
+    fn bodies_without_contacts_are_islands_of_their_own() {
+        assert_eq!(
+            sorted(find_islands(3, &[])),
+            vec![vec![0], vec![1], vec![2]]
+        );
+    }
+
+    #[test]
+    fn bodies_touching_through_other_bodies_share_an_island() {
+        let islands = find_islands(6, &[(0, 1), (4, 2), (1, 4), (3, 5)]);
+        assert_eq!(sorted(islands), vec![vec![0, 1, 2, 4], vec![3, 5]]);
+    }
+
+    #[test]
+    fn the_same_contact_twice_changes_nothing() {
+        let islands = find_islands(3, &[(0, 1), (1, 0), (0, 1)]);
+        assert_eq!(sorted(islands), vec![vec![0, 1], vec![2]]);
+    }
+}
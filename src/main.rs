@@ -1,23 +1,63 @@
 use macroquad::prelude::*;
 
+mod body_set;
+mod camera;
+mod collision;
+mod contact_solver;
+mod debug_draw;
+mod editor;
+mod energy;
 mod engine;
+mod fluid;
+mod force_field;
+mod headless;
+mod island;
+mod mouse_joint;
+mod plot;
+mod replay;
 mod rigid_body;
 mod rigid_circle;
+mod rigid_polygon;
 mod rigid_rectangle;
 mod rigid_spring;
 mod scenes;
+mod snapshot;
+mod spatial_grid;
+mod sph;
+mod tools;
+mod trails;
+mod trajectory;
+mod world;
 
 use engine::Engine;
-use scenes::Scene;
+use scenes::SceneList;
 
-pub const SCREEN_X_INCREASE: f32 = 1.7;
-pub const SCREEN_SIZE: Vec2 = vec2(700. * SCREEN_X_INCREASE, 700.);
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "Physics Engine".to_owned(),
+        window_width: 1190,
+        window_height: 700,
+        window_resizable: true,
+        ..Default::default()
+    }
+}
 
-#[macroquad::main("Physics Engine")]
-async fn main() {
-    request_new_screen_size(SCREEN_SIZE.x, SCREEN_SIZE.y);
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // Runs without opening a window
+    if args.first().is_some_and(|arg| arg == "--headless") {
+        if let Err(err) = headless::run(&args[1..]) {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+    macroquad::Window::from_config(window_conf(), run());
+}
 
-    let mut engine = Engine::new(Scene::FallingRectangles);
+async fn run() {
+    let scene_list = SceneList::load();
+    let mut engine = Engine::new(scene_list.get_first(), scene_list);
 
     loop {
         clear_background(LIGHTGRAY);
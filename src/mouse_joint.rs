@@ -0,0 +1,77 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{body_set::BodyHandle, camera::Camera, rigid_body::RigidBody};
+
+// The joint can't accelerate a body faster than this many m/s², so heavy bodies can't be flung endlessly
+const MAX_ACCELERATION: f32 = 500.;
+
+// Soft spring from the mouse to the point of a body that was grabbed.
+// It works with impulses like the contact solver, so the body keeps whatever
+// velocity it had when it gets released.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MouseJoint {
+    pub body: BodyHandle,
+    // Where the body was grabbed, relative to its position. Bodies don't rotate, so this stays the same
+    anchor: Vec2,
+    pub target: Vec2,
+    // How fast the body follows the mouse in Hz
+    frequency: f32,
+    // 1 stops without overshooting, lower values make it wobble
+    damping_ratio: f32,
+}
+impl MouseJoint {
+    pub fn new(
+        body: BodyHandle,
+        rb: &dyn RigidBody,
+        grab_point: Vec2,
+        frequency: f32,
+        damping_ratio: f32,
+    ) -> Self {
+        Self {
+            body,
+            anchor: grab_point - rb.get_pos(),
+            target: grab_point,
+            frequency,
+            damping_ratio,
+        }
+    }
+
+    pub fn get_anchor(&self, rb: &dyn RigidBody) -> Vec2 {
+        rb.get_pos() + self.anchor
+    }
+
+    // Pulls the anchor towards the target. A stiff spring would blow up with large time steps,
+    // so the spring and damper are turned into a soft constraint that stays stable for any step
+    pub fn apply(&self, rb: &mut dyn RigidBody, delta_time: f32) {
+        if delta_time <= 0. {
+            return;
+        }
+        let mass = rb.get_mass();
+        let omega = 2. * std::f32::consts::PI * self.frequency;
+        // Spring constant and damping coefficient of a mass-spring-damper with this frequency and damping
+        let k = mass * omega * omega;
+        let c = 2. * mass * self.damping_ratio * omega;
+
+        // How fast the error gets fixed, and how much the constraint gives way
+        let gamma = 1. / (delta_time * (c + delta_time * k));
+        let beta = delta_time * k * gamma;
+        let effective_mass = 1. / (1. / mass + gamma);
+
+        let error = self.get_anchor(rb) - self.target;
+        let impulse = -effective_mass * (rb.get_vel() + beta * error);
+        let impulse = impulse.clamp_length_max(MAX_ACCELERATION * mass * delta_time);
+
+        rb.set_vel(rb.get_vel() + impulse / mass);
+        rb.set_sleeping(false);
+        rb.set_sleep_timer(0.);
+    }
+
+    pub fn draw(&self, rb: &dyn RigidBody, camera: &Camera) {
+        let anchor = camera.world_to_screen(self.get_anchor(rb));
+        let target = camera.world_to_screen(self.target);
+        draw_line(anchor.x, anchor.y, target.x, target.y, 2., DARKGREEN);
+        draw_circle(anchor.x, anchor.y, 4., DARKGREEN);
+        draw_circle_lines(target.x, target.y, 6., 2., DARKGREEN);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::VecDeque;
+
+use egui_macroquad::egui::{
+    self,
+    plot::{Legend, Line, Plot, PlotPoints},
+    Context,
+};
+use macroquad::prelude::*;
+
+use crate::{
+    body_set::{BodyHandle, BodySet},
+    rigid_body::RigidBody,
+};
+
+// Points older than this many seconds scroll out of the plot
+const HISTORY: f32 = 10.;
+
+// What a series shows about its body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    PosX,
+    PosY,
+    Speed,
+    Acceleration,
+    ForceX,
+    ForceY,
+    Gravity,
+    AirResistanceX,
+    AirResistanceY,
+    Spring,
+    Buoyancy,
+    FieldX,
+    FieldY,
+    KineticEnergy,
+}
+impl Quantity {
+    const ALL: [Quantity; 14] = [
+        Quantity::PosX,
+        Quantity::PosY,
+        Quantity::Speed,
+        Quantity::Acceleration,
+        Quantity::ForceX,
+        Quantity::ForceY,
+        Quantity::Gravity,
+        Quantity::AirResistanceX,
+        Quantity::AirResistanceY,
+        Quantity::Spring,
+        Quantity::Buoyancy,
+        Quantity::FieldX,
+        Quantity::FieldY,
+        Quantity::KineticEnergy,
+    ];
+
+    fn get_name(self) -> &'static str {
+        match self {
+            Quantity::PosX => "x (m)",
+            Quantity::PosY => "y (m)",
+            Quantity::Speed => "speed (m/s)",
+            Quantity::Acceleration => "acceleration (m/s²)",
+            Quantity::ForceX => "F_res x (N)",
+            Quantity::ForceY => "F_res y (N)",
+            Quantity::Gravity => "F_g (N)",
+            Quantity::AirResistanceX => "F_air x (N)",
+            Quantity::AirResistanceY => "F_air y (N)",
+            Quantity::Spring => "F_spring (N)",
+            Quantity::Buoyancy => "F_b (N)",
+            Quantity::FieldX => "F_field x (N)",
+            Quantity::FieldY => "F_field y (N)",
+            Quantity::KineticEnergy => "E_k (J)",
+        }
+    }
+
+    // None if the body doesn't have this force
+    fn get_value(self, rb: &dyn RigidBody, acceleration: f32) -> Option<f32> {
+        let forces = rb.get_forces();
+        match self {
+            Quantity::PosX => Some(rb.get_pos().x),
+            Quantity::PosY => Some(rb.get_pos().y),
+            Quantity::Speed => Some(rb.get_vel().length()),
+            Quantity::Acceleration => Some(acceleration),
+            Quantity::ForceX => Some(forces.f_res.x),
+            Quantity::ForceY => Some(forces.f_res.y),
+            Quantity::Gravity => forces.f_g,
+            Quantity::AirResistanceX => forces.f_air.map(|f_air| f_air.x),
+            Quantity::AirResistanceY => forces.f_air.map(|f_air| f_air.y),
+            Quantity::Spring => forces.f_spring,
+            Quantity::Buoyancy => forces.f_buoyancy,
+            Quantity::FieldX => forces.f_field.map(|f_field| f_field.x),
+            Quantity::FieldY => forces.f_field.map(|f_field| f_field.y),
+            Quantity::KineticEnergy => Some(0.5 * rb.get_mass() * rb.get_vel().length_squared()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Series {
+    handle: BodyHandle,
+    quantity: Quantity,
+    // (time, value)
+    points: VecDeque<[f64; 2]>,
+    // Velocity after the last step, the acceleration comes from how much it changed
+    last_vel: Option<Vec2>,
+}
+
+// Graphs of body quantities over time, in their own window
+pub struct PlotPanel {
+    pub open: bool,
+    series: Vec<Series>,
+    // The graphs stop moving while the simulation goes on
+    frozen: bool,
+    // Time simulated since the panel was created
+    time: f32,
+    // What the next added series will show
+    handle: Option<BodyHandle>,
+    quantity: Quantity,
+}
+impl PlotPanel {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            series: Vec::new(),
+            frozen: false,
+            time: 0.,
+            handle: None,
+            quantity: Quantity::PosY,
+        }
+    }
+
+    // Has to be called right after every step
+    pub fn record_step(&mut self, delta_time: f32, rigid_bodies: &BodySet) {
+        // The acceleration can't be measured without any time passing
+        if delta_time == 0. {
+            return;
+        }
+        self.time += delta_time;
+        let time = self.time as f64;
+        for series in &mut self.series {
+            // Removed bodies keep what they had until it scrolls out
+            if let Some(rb) = rigid_bodies.get(series.handle) {
+                let vel = rb.get_vel();
+                let acceleration = series
+                    .last_vel
+                    .map_or(0., |last_vel| (vel - last_vel).length() / delta_time.abs());
+                series.last_vel = Some(vel);
+                match series.quantity.get_value(rb, acceleration) {
+                    Some(value) if !self.frozen => series.points.push_back([time, value as f64]),
+                    _ => {}
+                }
+            }
+            if self.frozen {
+                continue;
+            }
+            while series
+                .points
+                .front()
+                .is_some_and(|point| point[0] < time - HISTORY as f64)
+            {
+                series.points.pop_front();
+            }
+        }
+    }
+
+    pub fn update_ui(
+        &mut self,
+        egui_ctx: &Context,
+        rigid_bodies: &BodySet,
+        selected: Option<BodyHandle>,
+    ) {
+        let mut open = self.open;
+        egui::Window::new("Plot")
+            .open(&mut open)
+            .show(egui_ctx, |ui| {
+                // Start with the selected body, or whatever is there if the chosen one is gone
+                if self
+                    .handle
+                    .is_none_or(|handle| rigid_bodies.get(handle).is_none())
+                {
+                    self.handle = selected.or_else(|| {
+                        rigid_bodies
+                            .iter_with_handles()
+                            .next()
+                            .map(|(handle, _)| handle)
+                    });
+                }
+
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source("plot body")
+                        .selected_text(
+                            self.handle
+                                .map_or("-".to_owned(), |handle| format!("Body {handle}")),
+                        )
+                        .show_ui(ui, |ui| {
+                            for (handle, _) in rigid_bodies.iter_with_handles() {
+                                ui.selectable_value(
+                                    &mut self.handle,
+                                    Some(handle),
+                                    format!("Body {handle}"),
+                                );
+                            }
+                        });
+                    egui::ComboBox::from_id_source("plot quantity")
+                        .selected_text(self.quantity.get_name())
+                        .show_ui(ui, |ui| {
+                            for quantity in Quantity::ALL {
+                                ui.selectable_value(
+                                    &mut self.quantity,
+                                    quantity,
+                                    quantity.get_name(),
+                                );
+                            }
+                        });
+                    if let Some(handle) = self.handle {
+                        if ui.button("Add").clicked() {
+                            self.add_series(handle);
+                        }
+                    }
+                });
+
+                let mut removed = None;
+                for (i, series) in self.series.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(get_series_name(series));
+                        if ui.small_button("x").on_hover_text("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = removed {
+                    self.series.remove(i);
+                }
+
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.frozen, "freeze")
+                        .on_hover_text("Keep showing the graphs the way they are now");
+                    if ui.button("Clear").clicked() {
+                        for series in &mut self.series {
+                            series.points.clear();
+                        }
+                    }
+                });
+
+                Plot::new("body plot")
+                    .legend(Legend::default())
+                    .height(200.)
+                    .show(ui, |plot_ui| {
+                        for series in &self.series {
+                            let points: PlotPoints = series.points.iter().copied().collect();
+                            plot_ui.line(Line::new(points).name(get_series_name(series)));
+                        }
+                    });
+            });
+        self.open = open;
+    }
+
+    fn add_series(&mut self, handle: BodyHandle) {
+        let exists = self
+            .series
+            .iter()
+            .any(|series| series.handle == handle && series.quantity == self.quantity);
+        if !exists {
+            self.series.push(Series {
+                handle,
+                quantity: self.quantity,
+                points: VecDeque::new(),
+                last_vel: None,
+            });
+        }
+    }
+}
+
+fn get_series_name(series: &Series) -> String {
+    format!("{} of body {}", series.quantity.get_name(), series.handle)
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::Hasher,
+    io::{self, Write},
+    path::Path,
+};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body_set::BodySet, contact_solver::ContactSolver, engine::Variables, mouse_joint::MouseJoint,
+    snapshot::Snapshot, sph::SphSettings,
+};
+
+// Part of the engine the user can change between two steps. Sliders, spawning, dragging and
+// the editor all change one of these, so only the new value has to be recorded
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ReplayEvent {
+    Bodies(BodySet),
+    Variables(Variables),
+    Solver(Box<ContactSolver>),
+    SphSettings(SphSettings),
+    SpeedLimit(Option<f32>),
+    SleepingEnabled(bool),
+    MouseJoint(Option<MouseJoint>),
+}
+
+// Which part of the engine an event replaces
+#[derive(Debug, Clone, Copy)]
+pub enum ReplayPart {
+    Bodies,
+    Variables,
+    Solver,
+    SphSettings,
+    SpeedLimit,
+    SleepingEnabled,
+    MouseJoint,
+}
+impl ReplayPart {
+    pub const ALL: [ReplayPart; 7] = [
+        ReplayPart::Bodies,
+        ReplayPart::Variables,
+        ReplayPart::Solver,
+        ReplayPart::SphSettings,
+        ReplayPart::SpeedLimit,
+        ReplayPart::SleepingEnabled,
+        ReplayPart::MouseJoint,
+    ];
+}
+
+// Everything needed to run a simulation again exactly the same way
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    start: Snapshot,
+    // Frames don't take the same time, so the length of every step is needed
+    steps: Vec<f32>,
+    // What the user changed right before the step with this index
+    events: Vec<(usize, ReplayEvent)>,
+    // Where the bodies ended up, to check that playing it back gave the same result
+    final_positions: Vec<Vec2>,
+}
+impl Replay {
+    pub fn get_start(&self) -> &Snapshot {
+        &self.start
+    }
+
+    pub fn get_step_count(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        fs::write(path, ron).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let ron = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut replay: Replay =
+            ron::from_str(&ron).map_err(|err| format!("{}: {err}", path.display()))?;
+        let start = &mut replay.start;
+        start.scene.set_file_name(&start.scene_file_name);
+        Ok(replay)
+    }
+}
+
+pub struct ReplayRecorder {
+    replay: Replay,
+    // Hash of every part of the engine when it was last seen, in the order of ReplayPart::ALL, to
+    // find out which ones the user changed. None for parts the engine doesn't have
+    last_seen: Vec<Option<u64>>,
+}
+impl ReplayRecorder {
+    pub fn new(start: Snapshot, hashes: Vec<Option<u64>>) -> Self {
+        Self {
+            replay: Replay {
+                start,
+                steps: Vec::new(),
+                events: Vec::new(),
+                final_positions: Vec::new(),
+            },
+            last_seen: hashes,
+        }
+    }
+
+    // Has to be called right before every step, with the hashes of the parts the way they are
+    // now. Only the parts that changed are copied, with `get_event`
+    pub fn record_step(
+        &mut self,
+        delta_time: f32,
+        hashes: Vec<Option<u64>>,
+        get_event: impl Fn(ReplayPart) -> Option<ReplayEvent>,
+    ) {
+        let step = self.replay.steps.len();
+        for ((part, last_seen), hash) in ReplayPart::ALL
+            .into_iter()
+            .zip(&self.last_seen)
+            .zip(&hashes)
+        {
+            if hash.is_some() && hash != last_seen {
+                if let Some(event) = get_event(part) {
+                    self.replay.events.push((step, event));
+                }
+            }
+        }
+        self.last_seen = hashes;
+        self.replay.steps.push(delta_time);
+    }
+
+    // Has to be called right after every step. What the step changed happens again when
+    // playing it back, so it isn't recorded
+    pub fn skip_changes(&mut self, hashes: Vec<Option<u64>>) {
+        self.last_seen = hashes;
+    }
+
+    pub fn get_step_count(&self) -> usize {
+        self.replay.steps.len()
+    }
+
+    pub fn finish(mut self, final_positions: Vec<Vec2>) -> Replay {
+        self.replay.final_positions = final_positions;
+        self.replay
+    }
+}
+
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_step: usize,
+    next_event: usize,
+}
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next_step: 0,
+            next_event: 0,
+        }
+    }
+
+    // The changes to make before the next step and how long it is, None once it's over
+    pub fn next_step(&mut self) -> Option<(Vec<ReplayEvent>, f32)> {
+        let delta_time = *self.replay.steps.get(self.next_step)?;
+        let mut events = Vec::new();
+        while let Some((step, event)) = self.replay.events.get(self.next_event) {
+            if *step != self.next_step {
+                break;
+            }
+            events.push(event.clone());
+            self.next_event += 1;
+        }
+        self.next_step += 1;
+        Some((events, delta_time))
+    }
+
+    // Returns (steps played, total steps)
+    pub fn get_progress(&self) -> (usize, usize) {
+        (self.next_step, self.replay.get_step_count())
+    }
+
+    pub fn matches_recording(&self, final_positions: &[Vec2]) -> bool {
+        self.replay.final_positions == final_positions
+    }
+}
+
+// Hash of a part as RON, written straight into the hasher so the part doesn't have to be copied
+pub fn hash_part(part: &impl Serialize) -> Result<u64, String> {
+    let mut writer = HashWriter(DefaultHasher::new());
+    ron::ser::to_writer(&mut writer, part).map_err(|err| err.to_string())?;
+    Ok(writer.0.finish())
+}
+
+struct HashWriter(DefaultHasher);
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_parts_hash_the_same() {
+        let vars = Variables::new(Some(9.81), Some(0.), None);
+        assert_eq!(hash_part(&vars), hash_part(&vars.clone()));
+        assert_eq!(hash_part(&Some(1.5f32)), hash_part(&Some(1.5f32)));
+    }
+
+    #[test]
+    fn changed_parts_hash_differently() {
+        let vars = Variables::new(Some(9.81), Some(0.), None);
+        let changed = Variables::new(Some(9.8), Some(0.), None);
+        assert_ne!(hash_part(&vars), hash_part(&changed));
+        assert_ne!(hash_part(&Some(1.5f32)), hash_part(&None::<f32>));
+        assert_ne!(hash_part(&true), hash_part(&false));
+    }
+}
@@ -1,15 +1,30 @@
 use egui_macroquad::egui::{self, Context, Ui};
-use macroquad::prelude::{vec2, Vec2};
+use std::f32::consts::PI;
 
-use crate::engine::Variables;
+use macroquad::prelude::{vec2, Color, Vec2};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    body_set::BodyHandle,
+    camera::Camera,
+    collision::{CollisionFilter, Shape},
+    engine::Variables,
+    rigid_circle::RigidCircle,
+    rigid_polygon::RigidPolygon,
+    rigid_rectangle::RigidSquare,
+    rigid_spring::RigidSpring,
+};
 
-pub type RigidBodies = Vec<Box<dyn RigidBody>>;
 const DIGITS_AFTER_DECIMAL: usize = 0;
 
+// The world is 2D, so bodies are treated as if they extend this far into the screen.
+// This way areas and volumes still have their usual units
+pub const BODY_DEPTH: f32 = 1.;
+
 pub trait RigidBody {
-    fn apply_forces(&mut self, vars: Variables, delta_time: f32, scene_size: Vec2);
-    fn draw(&self, metre_in_pixels: Vec2);
-    fn update_based_on_ui(&mut self, egui_ctx: &Context, index: usize);
+    fn apply_forces(&mut self, vars: &Variables, delta_time: f32, scene_size: Vec2);
+    fn draw(&self, camera: &Camera);
+    fn update_based_on_ui(&mut self, egui_ctx: &Context, handle: BodyHandle);
     fn get_type(&self) -> RigidBodyType;
     fn get_enabled(&self) -> bool;
     fn get_pos(&self) -> Vec2;
@@ -17,50 +32,90 @@ pub trait RigidBody {
     fn get_mass(&self) -> f32;
     fn get_radius(&self) -> Option<f32>;
     fn get_size(&self) -> Option<Vec2>;
+    // Corners relative to the position, going counter clockwise
+    fn get_vertices(&self) -> Option<Vec<Vec2>>;
     fn get_restitution(&self) -> Option<f32>;
+    fn get_sleeping(&self) -> bool;
+    fn get_bullet(&self) -> bool;
+    fn get_sensor(&self) -> bool;
+    fn get_collision_filter(&self) -> CollisionFilter;
+    fn get_sleep_timer(&self) -> f32;
+    fn get_forces(&self) -> Forces;
     fn set_vel(&mut self, new_vel: Vec2);
     fn set_pos(&mut self, new_pos: Vec2);
-    fn as_trait(&self) -> &dyn RigidBody;
-
-    fn colliding(&self, rb1: &Box<dyn RigidBody>) -> bool {
-        if self.get_type() == RigidBodyType::Circle && rb1.get_type() == RigidBodyType::Circle {
-            if let Some(radius_0) = self.get_radius() {
-                if let Some(radius_1) = rb1.get_radius() {
-                    let dist_between_circles = self.get_pos().distance(rb1.get_pos());
-                    return dist_between_circles < radius_0 + radius_1;
-                }
-            }
-        } else if (self.get_type() == RigidBodyType::Square
-            && rb1.get_type() == RigidBodyType::Circle)
-            || (self.get_type() == RigidBodyType::Circle && rb1.get_type() == RigidBodyType::Square)
-        {
-            let (size, radius) = get_size_and_radius(self.as_trait(), rb1);
-
-            let rect_size_half = size * 0.5;
-            let circle_dist = vec2(
-                (rb1.get_pos().x - rect_size_half.x - self.get_pos().x).abs(),
-                (rb1.get_pos().y + rect_size_half.y - self.get_pos().y).abs(),
-            );
-
-            // Circle is too far away from rect to be colliding
-            if circle_dist.x > (rect_size_half.x + radius)
-                || circle_dist.y > (rect_size_half.y + radius)
-            {
-                return false;
+    fn set_sleeping(&mut self, sleeping: bool);
+    fn set_bullet(&mut self, bullet: bool);
+    fn set_sensor(&mut self, sensor: bool);
+    fn set_collision_filter(&mut self, filter: CollisionFilter);
+    fn set_sleep_timer(&mut self, sleep_timer: f32);
+    fn set_restitution(&mut self, restitution: f32);
+    // Stretches the shape by `factor` along each axis, relative to the position
+    fn scale(&mut self, factor: Vec2);
+    // Turns the shape counter clockwise around the position. Only polygons can be rotated,
+    // the other shapes always stay lined up with the axes
+    fn rotate(&mut self, angle: f32);
+    // Moves the body without it springing back, unlike set_pos
+    fn translate(&mut self, offset: Vec2) {
+        self.set_pos(self.get_pos() + offset);
+    }
+    fn duplicate(&self) -> Box<dyn RigidBody>;
+    // A copy of everything in the body, used to save it
+    fn get_state(&self) -> BodyState;
+
+    // Springs return None, they don't collide with anything
+    fn get_shape(&self) -> Option<Shape> {
+        let pos = self.get_pos();
+        match self.get_type() {
+            RigidBodyType::Circle => Some(Shape::Circle {
+                center: pos,
+                radius: self.get_radius()?,
+            }),
+            // Rectangles are positioned by their top left corner
+            RigidBodyType::Square => {
+                let size = self.get_size()?;
+                Some(Shape::Rect {
+                    min: vec2(pos.x, pos.y - size.y),
+                    max: vec2(pos.x + size.x, pos.y),
+                })
             }
+            RigidBodyType::Polygon => Some(Shape::Polygon {
+                vertices: self.get_vertices()?.iter().map(|&v| pos + v).collect(),
+            }),
+            RigidBodyType::Spring => None,
+        }
+    }
 
-            // Circle is definitely colliding
-            if circle_dist.x <= rect_size_half.x || circle_dist.y <= rect_size_half.y {
-                return true;
+    // Springs are positioned like rectangles, by the top left corner of their weight
+    fn get_center_of_mass(&self) -> Vec2 {
+        match self.get_shape() {
+            Some(shape) => shape.get_centroid(),
+            None => {
+                let size = self.get_size().unwrap_or_default();
+                self.get_pos() + vec2(size.x, -size.y) * 0.5
             }
+        }
+    }
 
-            // Check for corner case
-            let corner_dist_square = (circle_dist.x - rect_size_half.x).powi(2)
-                + (circle_dist.y - rect_size_half.y).powi(2);
+    // Energy stored by stretching or compressing the body, only springs have any
+    fn get_spring_energy(&self) -> f32 {
+        0.
+    }
 
-            return corner_dist_square <= radius.powi(2);
-        }
-        false
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.get_shape()
+            .is_some_and(|shape| shape.contains_point(point))
+    }
+
+    fn update_collision_mode_ui(&mut self, ui: &mut Ui) {
+        let mut bullet = self.get_bullet();
+        ui.checkbox(&mut bullet, "bullet").on_hover_text(
+            "Use continuous collision detection so it can't pass through thin bodies at high speeds",
+        );
+        self.set_bullet(bullet);
+        let mut sensor = self.get_sensor();
+        ui.checkbox(&mut sensor, "sensor")
+            .on_hover_text("Detect overlapping bodies without colliding with them");
+        self.set_sensor(sensor);
     }
 
     fn update_default_properties_ui(&mut self, ui: &mut Ui, mass: &mut f32, default_pos: Vec2) {
@@ -71,12 +126,28 @@ pub trait RigidBody {
             ui.label(format!("Size: {} m", size));
         }
 
+        if self.get_sleeping() {
+            ui.horizontal(|ui| {
+                ui.label("Sleeping");
+                if ui.button("Wake up").clicked() {
+                    self.set_sleeping(false);
+                    self.set_sleep_timer(0.);
+                }
+            });
+        }
+
         ui.horizontal(|ui| {
             ui.label("Mass:");
             ui.add(egui::Slider::new(mass, (0.1)..=300.));
             ui.label("kg");
         });
 
+        if self.get_shape().is_some() {
+            let mut filter = self.get_collision_filter();
+            filter.update_ui(ui);
+            self.set_collision_filter(filter);
+        }
+
         ui.horizontal(|ui| {
             ui.label(format!(
                 "Velocity: {} m/s",
@@ -103,15 +174,54 @@ pub trait RigidBody {
 pub enum RigidBodyType {
     Square,
     Circle,
+    Polygon,
     Spring,
 }
 
-#[derive(Debug, Clone, Copy)]
+// Every kind of body, so a `Box<dyn RigidBody>` can be saved and loaded again
+#[derive(Clone, Serialize, Deserialize)]
+pub enum BodyState {
+    Square(RigidSquare),
+    Circle(RigidCircle),
+    Polygon(RigidPolygon),
+    Spring(RigidSpring),
+}
+impl BodyState {
+    pub fn into_body(self) -> Box<dyn RigidBody> {
+        match self {
+            BodyState::Square(rb) => Box::new(rb),
+            BodyState::Circle(rb) => Box::new(rb),
+            BodyState::Polygon(rb) => Box::new(rb),
+            BodyState::Spring(rb) => Box::new(rb),
+        }
+    }
+}
+impl Clone for Box<dyn RigidBody> {
+    fn clone(&self) -> Self {
+        self.duplicate()
+    }
+}
+impl Serialize for Box<dyn RigidBody> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get_state().serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Box<dyn RigidBody> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        BodyState::deserialize(deserializer).map(BodyState::into_body)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Forces {
     pub f_res: Vec2,
     pub f_g: Option<f32>,
     pub f_air: Option<Vec2>,
     pub f_spring: Option<f32>,
+    pub f_buoyancy: Option<f32>,
+    pub f_field: Option<Vec2>,
+    pub terminal_vel: Option<f32>,
+    pub submerged_fraction: Option<f32>,
 }
 impl Forces {
     pub fn new(f_g_used: bool, f_air_used: bool, f_spring_used: bool) -> Self {
@@ -123,6 +233,10 @@ impl Forces {
             f_g,
             f_air,
             f_spring,
+            f_buoyancy: None,
+            f_field: None,
+            terminal_vel: None,
+            submerged_fraction: None,
         }
     }
     pub fn display_ui(&self, ui: &mut Ui) {
@@ -139,13 +253,35 @@ impl Forces {
                 ));
             }
             if let Some(f_air) = self.f_air {
-                ui.label("Air resistance: c * A * v*v =");
+                ui.label("Drag: -(b * |v| + 0.5 * ρ * Cd * A * |v|²) * v̂ =")
+                    .on_hover_text("b = 3 * π * μ * w is the linear (Stokes) drag");
                 ui.label(format!(
                     "{} = {} N",
                     f_air.format(DIGITS_AFTER_DECIMAL),
                     f_air.length().format(DIGITS_AFTER_DECIMAL)
                 ));
             }
+            if let Some(terminal_vel) = self.terminal_vel {
+                ui.label(format!("Terminal velocity: {} m/s", terminal_vel.format(2)));
+            }
+            if let Some(f_buoyancy) = self.f_buoyancy {
+                ui.label(format!(
+                    "Buoyancy: ρ * V * g = {} N",
+                    f_buoyancy.format(DIGITS_AFTER_DECIMAL)
+                ));
+            }
+            if let Some(f_field) = self.f_field {
+                ui.label(format!(
+                    "Force fields: m * a = {} N",
+                    f_field.format(DIGITS_AFTER_DECIMAL)
+                ));
+            }
+            if let Some(submerged_fraction) = self.submerged_fraction {
+                ui.label(format!(
+                    "Submerged: {} %",
+                    (submerged_fraction * 100.).format(DIGITS_AFTER_DECIMAL)
+                ));
+            }
             if let Some(f_spring) = self.f_spring {
                 ui.label(format!(
                     "Spring force: c * u = {} N",
@@ -156,25 +292,100 @@ impl Forces {
     }
 }
 
-fn get_size_and_radius(rb0: &dyn RigidBody, rb1: &Box<dyn RigidBody>) -> (Vec2, f32) {
-    let mut new_size = Vec2::ZERO;
-    let mut new_radius = 0.;
-    if let Some(s) = rb0.get_size() {
-        new_size = s;
-        if let Some(r) = rb1.get_radius() {
-            new_radius = r;
-        }
+// Sleeping bodies are drawn faded, so it's clear which ones aren't being simulated
+pub fn get_body_color(color: Color, sleeping: bool, sensor: bool) -> Color {
+    // Sensors don't push anything away, so they are drawn see-through
+    let a = if sensor { color.a * 0.3 } else { color.a };
+    if sleeping {
+        Color::new(
+            color.r * 0.4 + 0.35,
+            color.g * 0.4 + 0.35,
+            color.b * 0.4 + 0.35,
+            a,
+        )
+    } else {
+        Color { a, ..color }
     }
-    if let Some(s) = rb1.get_size() {
-        new_size = s;
-        if let Some(r) = rb0.get_radius() {
-            new_radius = r;
-        }
+}
+
+// Drag exerted by the surrounding fluid on a body moving with `vel`, always opposite to the velocity.
+// `width` is the width of the body perpendicular to the velocity.
+pub fn calculate_drag(
+    vars: &Variables,
+    vel: Vec2,
+    drag_coefficient: f32,
+    width: f32,
+) -> Option<Vec2> {
+    if vars.rho.is_none() && vars.mu.is_none() {
+        return None;
     }
-    if new_size == Vec2::ZERO || new_radius == 0. {
-        panic!("Both properties are None")
+    let rho = vars.rho.unwrap_or(0.);
+    let mu = vars.mu.unwrap_or(0.);
+    Some(get_drag(rho, mu, vel, drag_coefficient, width))
+}
+
+// Drag in a fluid with density `rho` and viscosity `mu`
+pub fn get_drag(rho: f32, mu: f32, vel: Vec2, drag_coefficient: f32, width: f32) -> Vec2 {
+    let speed = vel.length();
+    if speed == 0. {
+        return Vec2::ZERO;
+    }
+    let (b, k) = get_drag_terms(rho, mu, drag_coefficient, width);
+
+    // F_drag = -(b * |v| + k * |v|^2) * v̂
+    -(b * speed + k * speed * speed) * vel / speed
+}
+
+// The speed at which the drag cancels out gravity for a body falling with `width` facing down,
+// None if there is nothing to stop the body from accelerating
+pub fn calculate_terminal_vel(
+    vars: &Variables,
+    mass: f32,
+    drag_coefficient: f32,
+    width: f32,
+) -> Option<f32> {
+    let f_g = mass * vars.g?.abs();
+    let rho = vars.rho.unwrap_or(0.);
+    let mu = vars.mu.unwrap_or(0.);
+    let (b, k) = get_drag_terms(rho, mu, drag_coefficient, width);
+
+    // Solve k * v^2 + b * v - m * g = 0 for v
+    let terminal_vel = if k != 0. {
+        (-b + (b * b + 4. * k * f_g).sqrt()) / (2. * k)
+    } else if b != 0. {
+        f_g / b
+    } else {
+        return None;
+    };
+
+    if terminal_vel.is_finite() && terminal_vel >= 0. {
+        Some(terminal_vel)
+    } else {
+        None
+    }
+}
+
+// Returns the linear and quadratic drag factors b and k
+fn get_drag_terms(rho: f32, mu: f32, drag_coefficient: f32, width: f32) -> (f32, f32) {
+    // Stokes drag b = 6 * pi * mu * r, with the radius being half the width
+    let b = 3. * PI * mu * width;
+    // Quadratic drag k = 0.5 * rho * Cd * A
+    let k = 0.5 * rho * drag_coefficient * width * BODY_DEPTH;
+    (b, k)
+}
+
+// Coefficient of restitution, bounciness/elasticity. From Newton's Law of Restitution
+// https://physics.stackexchange.com/questions/188030/newtons-law-of-restitution
+// e = relative speed after collision / relative speed before collision, a value of 1 means the objects lose no velocity
+pub fn get_combined_restitution(rb0: &dyn RigidBody, rb1: &dyn RigidBody) -> f32 {
+    let mut e = 1.;
+    if let Some(e1) = rb0.get_restitution() {
+        e = e1;
+        if let Some(e2) = rb1.get_restitution() {
+            e = f32::min(e1, e2);
+        }
     }
-    (new_size, new_radius)
+    e
 }
 
 pub trait Format {
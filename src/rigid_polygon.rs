@@ -0,0 +1,318 @@
+use std::f32::consts::TAU;
+
+use egui_macroquad::egui::{self, Context};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body_set::BodyHandle,
+    camera::Camera,
+    collision::CollisionFilter,
+    engine::Variables,
+    fluid::get_polygon_area,
+    force_field::get_field_force,
+    rigid_body::{
+        calculate_drag, calculate_terminal_vel, get_body_color, BodyState, Forces, RigidBody,
+        RigidBodyType,
+    },
+};
+
+// A convex polygon. It doesn't rotate, just like the other bodies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RigidPolygon {
+    enabled: bool,
+    sleeping: bool,
+    sleep_timer: f32,
+    bullet: bool,
+    sensor: bool,
+    collision_filter: CollisionFilter,
+    mass: f32,
+    restitution: f32,
+    drag_coefficient: f32,
+    pos: Vec2,
+    vel: Vec2,
+    // Corners relative to pos, going counter clockwise
+    vertices: Vec<Vec2>,
+    forces: Forces,
+    default_pos: Vec2,
+    default_mass: f32,
+}
+impl RigidPolygon {
+    // `vertices` are relative to `pos` and have to form a convex polygon
+    pub fn new(mass: f32, pos: Vec2, mut vertices: Vec<Vec2>) -> Self {
+        if get_polygon_area(&vertices) < 0. {
+            vertices.reverse();
+        }
+        let forces = Forces::new(true, true, false);
+        Self {
+            enabled: true,
+            sleeping: false,
+            sleep_timer: 0.,
+            bullet: false,
+            sensor: false,
+            collision_filter: CollisionFilter::default(),
+            mass,
+            restitution: 0.4,
+            drag_coefficient: 0.8,
+            pos,
+            vel: Vec2::ZERO,
+            vertices,
+            forces,
+            default_pos: pos,
+            default_mass: mass,
+        }
+    }
+
+    // Polygon with `sides` equal sides and its corners `radius` away from `pos`
+    pub fn new_regular(mass: f32, pos: Vec2, radius: f32, sides: usize) -> Self {
+        // Start at the top so triangles point up
+        let vertices = (0..sides)
+            .map(|i| Vec2::from_angle(TAU * i as f32 / sides as f32).rotate(vec2(0., radius)))
+            .collect();
+        Self::new(mass, pos, vertices)
+    }
+
+    // Smallest box around the corners relative to pos, as (min, max)
+    fn get_bounds(&self) -> (Vec2, Vec2) {
+        self.vertices.iter().fold(
+            (Vec2::splat(f32::INFINITY), Vec2::splat(f32::NEG_INFINITY)),
+            |(min, max), &v| (min.min(v), max.max(v)),
+        )
+    }
+
+    // Width of the polygon perpendicular to `dir`
+    fn get_width(&self, dir: Vec2) -> f32 {
+        let side = dir.perp();
+        let (min, max) = self
+            .vertices
+            .iter()
+            .map(|v| v.dot(side))
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+                (min.min(d), max.max(d))
+            });
+        max - min
+    }
+}
+impl RigidBody for RigidPolygon {
+    fn apply_forces(&mut self, vars: &Variables, delta_time: f32, scene_size: Vec2) {
+        let mut f_res = Vec2::ZERO;
+        let mut f_g = None;
+        let mut f_air = None;
+        let mut f_b = None;
+        let mut submerged_fraction = None;
+        let mut f_field = None;
+
+        if let Some(g) = vars.g {
+            // Fz = m * g
+            let f_gravity = g * self.mass;
+            f_res.y -= f_gravity;
+            f_g = Some(f_gravity);
+        }
+
+        // The width facing the fluid depends on the direction the polygon moves in
+        let width = self.get_width(self.vel.normalize_or_zero());
+        if let Some(f_drag) = calculate_drag(vars, self.vel, self.drag_coefficient, width) {
+            f_res += f_drag;
+            f_air = Some(f_drag);
+        }
+
+        if let Some(fluid) = vars.fluid {
+            let area = get_polygon_area(&self.vertices);
+            let corners: Vec<Vec2> = self.vertices.iter().map(|&v| self.pos + v).collect();
+            let submerged_area = fluid.get_submerged_area_polygon(&corners);
+            let (f_buoyancy, f_drag) = fluid.calculate_forces(
+                vars.g,
+                submerged_area,
+                area,
+                self.vel,
+                self.drag_coefficient,
+                width,
+            );
+            f_res.y += f_buoyancy;
+            f_res += f_drag;
+            f_air = Some(f_air.unwrap_or(Vec2::ZERO) + f_drag);
+            f_b = Some(f_buoyancy);
+            submerged_fraction = Some(submerged_area / area);
+        }
+
+        // The corners aren't placed around pos, so the field is sampled where the mass is
+        if let Some(f_fields) = get_field_force(&vars.fields, self.get_center_of_mass(), self.mass)
+        {
+            f_res += f_fields;
+            f_field = Some(f_fields);
+        }
+
+        //a = f / m
+        let acc = f_res / self.mass;
+
+        //v = u + a * dt
+        self.vel += acc * delta_time;
+
+        //p = p + v * dt
+        let next_pos = self.pos + self.vel * delta_time;
+
+        // Keep the corners inside the world, the floor is 1 m high
+        let (min, max) = self.get_bounds();
+        if next_pos.y + max.y > scene_size.y {
+            self.vel.y *= -self.restitution;
+            self.pos.y = scene_size.y - max.y;
+        } else if next_pos.y + min.y < 1. {
+            self.vel.y *= -self.restitution;
+            self.pos.y = 1. - min.y;
+        } else {
+            self.pos.y = next_pos.y;
+        }
+        if next_pos.x + max.x > scene_size.x {
+            self.vel.x *= -self.restitution;
+            self.pos.x = scene_size.x - max.x;
+        } else if next_pos.x + min.x < 0. {
+            self.vel.x *= -self.restitution;
+            self.pos.x = -min.x;
+        } else {
+            self.pos.x = next_pos.x;
+        }
+
+        self.forces.f_res = f_res;
+        self.forces.f_g = f_g;
+        self.forces.f_air = f_air;
+        self.forces.f_buoyancy = f_b;
+        self.forces.f_field = f_field;
+        self.forces.submerged_fraction = submerged_fraction;
+        self.forces.terminal_vel = calculate_terminal_vel(
+            vars,
+            self.mass,
+            self.drag_coefficient,
+            self.get_width(vec2(0., -1.)),
+        );
+    }
+
+    fn draw(&self, camera: &Camera) {
+        let color = get_body_color(RED, self.sleeping, self.sensor);
+        let to_screen = |v: Vec2| camera.world_to_screen(self.pos + v);
+        // Convex, so it can be drawn as a fan of triangles from the first corner
+        let first = to_screen(self.vertices[0]);
+        for edge in self.vertices[1..].windows(2) {
+            draw_triangle(first, to_screen(edge[0]), to_screen(edge[1]), color);
+        }
+    }
+
+    fn update_based_on_ui(&mut self, egui_ctx: &Context, handle: BodyHandle) {
+        egui::Window::new(format!("Polygon {handle}"))
+            .id(egui::Id::new(handle))
+            .show(egui_ctx, |ui| {
+                ui.set_max_width(200.);
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.enabled, "enabled");
+                    self.update_collision_mode_ui(ui);
+                    if ui.button("Reset all").clicked() {
+                        *self = RigidPolygon::new(
+                            self.default_mass,
+                            self.default_pos,
+                            self.vertices.clone(),
+                        );
+                    }
+                });
+
+                ui.collapsing("Show data", |ui| {
+                    ui.label(format!("Corners: {}", self.vertices.len()));
+                    ui.horizontal(|ui| {
+                        ui.label("Restitution:");
+                        ui.add(egui::Slider::new(&mut self.restitution, (0.1)..=1.));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Drag coefficient:");
+                        ui.add(egui::Slider::new(&mut self.drag_coefficient, (0.)..=2.));
+                    });
+
+                    let mut mass_copy = self.mass;
+                    self.update_default_properties_ui(ui, &mut mass_copy, self.default_pos);
+                    self.mass = mass_copy;
+                });
+                self.forces.display_ui(ui);
+            });
+    }
+    fn get_type(&self) -> RigidBodyType {
+        RigidBodyType::Polygon
+    }
+    fn get_enabled(&self) -> bool {
+        self.enabled
+    }
+    fn get_pos(&self) -> Vec2 {
+        self.pos
+    }
+    fn get_vel(&self) -> Vec2 {
+        self.vel
+    }
+    fn get_mass(&self) -> f32 {
+        self.mass
+    }
+    fn get_radius(&self) -> Option<f32> {
+        None
+    }
+    fn get_size(&self) -> Option<Vec2> {
+        None
+    }
+    fn get_vertices(&self) -> Option<Vec<Vec2>> {
+        Some(self.vertices.clone())
+    }
+    fn get_restitution(&self) -> Option<f32> {
+        Some(self.restitution)
+    }
+    fn get_sleeping(&self) -> bool {
+        self.sleeping
+    }
+    fn get_bullet(&self) -> bool {
+        self.bullet
+    }
+    fn get_sensor(&self) -> bool {
+        self.sensor
+    }
+    fn get_collision_filter(&self) -> CollisionFilter {
+        self.collision_filter
+    }
+    fn get_sleep_timer(&self) -> f32 {
+        self.sleep_timer
+    }
+    fn get_forces(&self) -> Forces {
+        self.forces
+    }
+    fn set_vel(&mut self, new_vel: Vec2) {
+        self.vel = new_vel;
+    }
+    fn set_pos(&mut self, new_pos: Vec2) {
+        self.pos = new_pos;
+    }
+    fn set_sleeping(&mut self, sleeping: bool) {
+        self.sleeping = sleeping;
+    }
+    fn set_bullet(&mut self, bullet: bool) {
+        self.bullet = bullet;
+    }
+    fn set_sensor(&mut self, sensor: bool) {
+        self.sensor = sensor;
+    }
+    fn set_collision_filter(&mut self, filter: CollisionFilter) {
+        self.collision_filter = filter;
+    }
+    fn set_sleep_timer(&mut self, sleep_timer: f32) {
+        self.sleep_timer = sleep_timer;
+    }
+    fn set_restitution(&mut self, restitution: f32) {
+        self.restitution = restitution;
+    }
+    fn scale(&mut self, factor: Vec2) {
+        self.vertices.iter_mut().for_each(|v| *v *= factor);
+    }
+    fn rotate(&mut self, angle: f32) {
+        let rotation = Vec2::from_angle(angle);
+        self.vertices
+            .iter_mut()
+            .for_each(|v| *v = rotation.rotate(*v));
+    }
+    fn duplicate(&self) -> Box<dyn RigidBody> {
+        Box::new(self.clone())
+    }
+    fn get_state(&self) -> BodyState {
+        BodyState::Polygon(self.clone())
+    }
+}
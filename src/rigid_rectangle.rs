@@ -1,18 +1,31 @@
 use egui_macroquad::egui::{self, Context};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    body_set::BodyHandle,
+    camera::Camera,
+    collision::CollisionFilter,
     engine::Variables,
-    rigid_body::{Forces, RigidBody, RigidBodyType},
-    SCREEN_SIZE,
+    force_field::get_field_force,
+    rigid_body::{
+        calculate_drag, calculate_terminal_vel, get_body_color, BodyState, Forces, RigidBody,
+        RigidBodyType,
+    },
 };
 use macroquad::math::Vec2;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct RigidSquare {
     enabled: bool,
+    sleeping: bool,
+    sleep_timer: f32,
+    bullet: bool,
+    sensor: bool,
+    collision_filter: CollisionFilter,
     mass: f32,
     restitution: f32,
+    drag_coefficient: f32,
     pos: Vec2,
     vel: Vec2,
     size: Vec2,
@@ -29,7 +42,13 @@ impl RigidSquare {
             vel: Vec2::ZERO,
             size,
             restitution: 0.4,
+            drag_coefficient: 1.05,
             enabled: true,
+            sleeping: false,
+            sleep_timer: 0.,
+            bullet: false,
+            sensor: false,
+            collision_filter: CollisionFilter::default(),
             forces,
             default_pos: pos,
             default_mass: mass,
@@ -37,10 +56,13 @@ impl RigidSquare {
     }
 }
 impl RigidBody for RigidSquare {
-    fn apply_forces(&mut self, vars: Variables, delta_time: f32, scene_size: Vec2) {
+    fn apply_forces(&mut self, vars: &Variables, delta_time: f32, scene_size: Vec2) {
         let mut f_res = Vec2::ZERO;
         let mut f_g = None;
         let mut f_air = None;
+        let mut f_b = None;
+        let mut submerged_fraction = None;
+        let mut f_field = None;
 
         if let Some(g) = vars.g {
             //Fz = m * g
@@ -49,11 +71,39 @@ impl RigidBody for RigidSquare {
             f_g = Some(f_gravity);
         }
 
-        if let Some(c) = vars.c {
-            //F_Air = 0.5 * p * A * v*v = c * A * v*v in our case because k = 0.5 * p
-            let f_air_resistance = c * self.size.x * self.vel * self.vel.abs();
-            f_res -= f_air_resistance;
-            f_air = Some(f_air_resistance)
+        // The width facing the fluid depends on the direction the rectangle moves in
+        let dir = self.vel.normalize_or_zero();
+        let width = self.size.x * dir.y.abs() + self.size.y * dir.x.abs();
+        if let Some(f_drag) = calculate_drag(vars, self.vel, self.drag_coefficient, width) {
+            f_res += f_drag;
+            f_air = Some(f_drag);
+        }
+
+        if let Some(fluid) = vars.fluid {
+            let area = self.size.x * self.size.y;
+            let submerged_area = fluid.get_submerged_area_rect(self.pos, self.size);
+            let (f_buoyancy, f_drag) = fluid.calculate_forces(
+                vars.g,
+                submerged_area,
+                area,
+                self.vel,
+                self.drag_coefficient,
+                width,
+            );
+            f_res.y += f_buoyancy;
+            f_res += f_drag;
+            f_air = Some(f_air.unwrap_or(Vec2::ZERO) + f_drag);
+            f_b = Some(f_buoyancy);
+            submerged_fraction = Some(submerged_area / area);
+        }
+
+        if let Some(f_fields) = get_field_force(
+            &vars.fields,
+            self.pos + vec2(self.size.x, -self.size.y) * 0.5,
+            self.mass,
+        ) {
+            f_res += f_fields;
+            f_field = Some(f_fields);
         }
 
         //a = f / m
@@ -109,40 +159,54 @@ impl RigidBody for RigidSquare {
         self.forces.f_res = f_res;
         self.forces.f_g = f_g;
         self.forces.f_air = f_air;
+        self.forces.f_buoyancy = f_b;
+        self.forces.f_field = f_field;
+        self.forces.submerged_fraction = submerged_fraction;
+        self.forces.terminal_vel =
+            calculate_terminal_vel(vars, self.mass, self.drag_coefficient, self.size.x);
     }
 
-    fn draw(&self, metre_in_pixels: Vec2) {
+    fn draw(&self, camera: &Camera) {
+        // The top left corner is the same in world and screen coordinates
+        let pos = camera.world_to_screen(self.pos);
         draw_rectangle(
-            self.pos.x * metre_in_pixels.x,
-            SCREEN_SIZE.y - self.pos.y * metre_in_pixels.y,
-            self.size.x * metre_in_pixels.x,
-            self.size.y * metre_in_pixels.y,
-            RED,
+            pos.x,
+            pos.y,
+            camera.metres_to_pixels(self.size.x),
+            camera.metres_to_pixels(self.size.y),
+            get_body_color(RED, self.sleeping, self.sensor),
         );
     }
 
-    fn update_based_on_ui(&mut self, egui_ctx: &Context, index: usize) {
-        egui::Window::new(format!("Rigidbody {index}")).show(egui_ctx, |ui| {
-            ui.set_max_width(200.);
-            ui.horizontal(|ui| {
-                ui.checkbox(&mut self.enabled, "enabled");
-                if ui.button("Reset all").clicked() {
-                    *self = RigidSquare::new(self.default_mass, self.default_pos, self.size);
-                }
-            });
-
-            ui.collapsing("Show data", |ui| {
+    fn update_based_on_ui(&mut self, egui_ctx: &Context, handle: BodyHandle) {
+        egui::Window::new(format!("Rigidbody {handle}"))
+            .id(egui::Id::new(handle))
+            .show(egui_ctx, |ui| {
+                ui.set_max_width(200.);
                 ui.horizontal(|ui| {
-                    ui.label("Restitution:");
-                    ui.add(egui::Slider::new(&mut self.restitution, (0.1)..=1.));
+                    ui.checkbox(&mut self.enabled, "enabled");
+                    self.update_collision_mode_ui(ui);
+                    if ui.button("Reset all").clicked() {
+                        *self = RigidSquare::new(self.default_mass, self.default_pos, self.size);
+                    }
+                });
+
+                ui.collapsing("Show data", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Restitution:");
+                        ui.add(egui::Slider::new(&mut self.restitution, (0.1)..=1.));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Drag coefficient:");
+                        ui.add(egui::Slider::new(&mut self.drag_coefficient, (0.)..=2.));
+                    });
+
+                    let mut mass_copy = self.mass;
+                    self.update_default_properties_ui(ui, &mut mass_copy, self.default_pos);
+                    self.mass = mass_copy;
                 });
-                
-                let mut mass_copy = self.mass;
-                self.update_default_properties_ui(ui, &mut mass_copy, self.default_pos);
-                self.mass = mass_copy;
+                self.forces.display_ui(ui);
             });
-            self.forces.display_ui(ui);
-        });
     }
     fn get_type(&self) -> RigidBodyType {
         RigidBodyType::Square
@@ -168,13 +232,59 @@ impl RigidBody for RigidSquare {
     fn set_pos(&mut self, new_pos: Vec2) {
         self.pos = new_pos;
     }
+    fn get_vertices(&self) -> Option<Vec<Vec2>> {
+        None
+    }
     fn get_size(&self) -> Option<Vec2> {
         Some(self.size)
     }
+    fn get_sleeping(&self) -> bool {
+        self.sleeping
+    }
+    fn get_bullet(&self) -> bool {
+        self.bullet
+    }
+    fn get_sleep_timer(&self) -> f32 {
+        self.sleep_timer
+    }
+    fn get_forces(&self) -> Forces {
+        self.forces
+    }
+    fn set_sleeping(&mut self, sleeping: bool) {
+        self.sleeping = sleeping;
+    }
+    fn get_collision_filter(&self) -> CollisionFilter {
+        self.collision_filter
+    }
+    fn set_collision_filter(&mut self, filter: CollisionFilter) {
+        self.collision_filter = filter;
+    }
+    fn get_sensor(&self) -> bool {
+        self.sensor
+    }
+    fn set_sensor(&mut self, sensor: bool) {
+        self.sensor = sensor;
+    }
+    fn set_bullet(&mut self, bullet: bool) {
+        self.bullet = bullet;
+    }
+    fn set_sleep_timer(&mut self, sleep_timer: f32) {
+        self.sleep_timer = sleep_timer;
+    }
+    fn set_restitution(&mut self, restitution: f32) {
+        self.restitution = restitution;
+    }
+    fn scale(&mut self, factor: Vec2) {
+        self.size *= factor;
+    }
+    fn rotate(&mut self, _angle: f32) {}
+    fn duplicate(&self) -> Box<dyn RigidBody> {
+        Box::new(*self)
+    }
+    fn get_state(&self) -> BodyState {
+        BodyState::Square(*self)
+    }
     fn get_restitution(&self) -> Option<f32> {
         Some(self.restitution)
     }
-    fn as_trait(&self) -> &dyn RigidBody {
-        self as &dyn RigidBody
-    }
 }
@@ -1,14 +1,20 @@
 use egui_macroquad::egui;
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    body_set::BodyHandle,
+    camera::Camera,
+    collision::CollisionFilter,
     engine::Variables,
-    rigid_body::{Forces, RigidBody, RigidBodyType},
-    SCREEN_SIZE,
+    rigid_body::{get_body_color, BodyState, Forces, RigidBody, RigidBodyType},
 };
 
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct RigidSpring {
     enabled: bool,
+    sleeping: bool,
+    sleep_timer: f32,
     mass: f32,
     pos: Vec2,
     vel: Vec2,
@@ -16,7 +22,6 @@ pub struct RigidSpring {
     equilibrium: f32,
     c: f32,
     u: f32,
-    clicked: bool,
     forces: Forces,
     default_mass: f32,
     default_pos: Vec2,
@@ -26,6 +31,8 @@ impl RigidSpring {
     pub fn new(mass: f32, pos: Vec2, size: Vec2) -> Self {
         Self {
             enabled: true,
+            sleeping: false,
+            sleep_timer: 0.,
             mass,
             pos,
             vel: Vec2::ZERO,
@@ -33,7 +40,6 @@ impl RigidSpring {
             equilibrium: pos.y,
             c: 5.,
             u: 0.,
-            clicked: false,
             forces: Forces::new(false, false, true),
             default_mass: mass,
             default_pos: pos,
@@ -42,33 +48,14 @@ impl RigidSpring {
     }
 }
 impl RigidBody for RigidSpring {
-    fn apply_forces(&mut self, _vars: Variables, delta_time: f32, scene_size: Vec2) {
-        if is_mouse_button_down(MouseButton::Left) && !self.clicked {
-            let mut mouse_pos = (mouse_position_local() + 1.) * 0.5 * scene_size;
-            mouse_pos.y = scene_size.y - mouse_pos.y;
-            if self.contains(mouse_pos) {
-                self.clicked = true;
-            }
-        }
-        if is_mouse_button_released(MouseButton::Left) {
-            self.clicked = false;
-        }
-        if self.clicked {
-            let mouse_y = scene_size.y - (mouse_position_local().y + 1.) * 0.5 * scene_size.y;
-            if mouse_y > 5. && mouse_y < self.equilibrium * 2. - 5. {
-                self.pos.y = mouse_y;
-            }
-        }
+    fn apply_forces(&mut self, _vars: &Variables, delta_time: f32, _scene_size: Vec2) {
         self.u = self.equilibrium - self.pos.y;
 
         let mut f_res = Vec2::ZERO;
-        let mut f_spring = 0.;
 
-        if !self.clicked {
-            //F_spring = c * u
-            f_spring = self.c * self.u;
-            f_res.y += f_spring;
-        }
+        //F_spring = c * u
+        let f_spring = self.c * self.u;
+        f_res.y += f_spring;
 
         //a = f / m
         let acc = f_res / self.mass;
@@ -88,60 +75,65 @@ impl RigidBody for RigidSpring {
         self.forces.f_spring = Some(f_spring);
     }
 
-    fn draw(&self, metre_in_pixels: Vec2) {
+    fn draw(&self, camera: &Camera) {
+        let color = get_body_color(BLACK, self.sleeping, false);
+        let pos = camera.world_to_screen(self.pos);
         draw_rectangle(
-            self.pos.x * metre_in_pixels.x,
-            SCREEN_SIZE.y - self.pos.y * metre_in_pixels.y,
-            self.size.x * metre_in_pixels.x,
-            self.size.y * metre_in_pixels.y,
-            BLACK,
+            pos.x,
+            pos.y,
+            camera.metres_to_pixels(self.size.x),
+            camera.metres_to_pixels(self.size.y),
+            color,
         );
+        // The spring goes down to the floor
+        let top = camera.world_to_screen(self.pos + vec2(self.size.x * 0.5, 0.));
+        let bottom = camera.world_to_screen(vec2(self.pos.x + self.size.x * 0.5, 1.));
+        draw_line(top.x, top.y, bottom.x, bottom.y, self.size.x * 0.6, color);
+        let left = camera.world_to_screen(vec2(self.pos.x + self.size.x * 0.2, 1.5));
+        let right = camera.world_to_screen(vec2(self.pos.x + self.size.x * 0.8, 1.5));
         draw_line(
-            (self.pos.x + self.size.x * 0.5) * metre_in_pixels.x,
-            SCREEN_SIZE.y - self.pos.y * metre_in_pixels.y,
-            (self.pos.x + self.size.x * 0.5) * metre_in_pixels.x,
-            SCREEN_SIZE.y - metre_in_pixels.y,
-            self.size.x * 0.6,
-            BLACK,
-        );
-        draw_line(
-            (self.pos.x + self.size.x * 0.2) * metre_in_pixels.x,
-            SCREEN_SIZE.y - metre_in_pixels.y * 1.5,
-            (self.pos.x + self.size.x * 0.8) * metre_in_pixels.x,
-            SCREEN_SIZE.y - metre_in_pixels.y * 1.5,
-            metre_in_pixels.y,
-            BLACK,
+            left.x,
+            left.y,
+            right.x,
+            right.y,
+            camera.metres_to_pixels(1.),
+            color,
         );
     }
 
-    fn update_based_on_ui(&mut self, egui_ctx: &egui_macroquad::egui::Context, index: usize) {
-        egui::Window::new(format!("Spring {index}")).show(egui_ctx, |ui| {
-            ui.set_max_width(200.);
-            ui.horizontal(|ui| {
-                ui.checkbox(&mut self.enabled, "enabled");
-                if ui.button("Reset all").clicked() {
-                    *self =
-                        RigidSpring::new(self.default_mass, self.default_pos, self.default_size);
-                }
-            });
+    fn update_based_on_ui(&mut self, egui_ctx: &egui_macroquad::egui::Context, handle: BodyHandle) {
+        egui::Window::new(format!("Spring {handle}"))
+            .id(egui::Id::new(handle))
+            .show(egui_ctx, |ui| {
+                ui.set_max_width(200.);
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.enabled, "enabled");
+                    if ui.button("Reset all").clicked() {
+                        *self = RigidSpring::new(
+                            self.default_mass,
+                            self.default_pos,
+                            self.default_size,
+                        );
+                    }
+                });
 
-            ui.collapsing("Show data", |ui| {
-                ui.heading("Data");
-                ui.label(format!("Size: {} m", self.size));
+                ui.collapsing("Show data", |ui| {
+                    ui.heading("Data");
+                    ui.label(format!("Size: {} m", self.size));
 
-                let mut mass_copy = self.mass;
-                self.update_default_properties_ui(ui, &mut mass_copy, self.default_pos);
-                self.mass = mass_copy;
+                    let mut mass_copy = self.mass;
+                    self.update_default_properties_ui(ui, &mut mass_copy, self.default_pos);
+                    self.mass = mass_copy;
 
-                ui.horizontal(|ui| {
-                    ui.label("c:");
-                    ui.add(egui::Slider::new(&mut self.c, (1.)..=30.));
-                    ui.label("N/m");
+                    ui.horizontal(|ui| {
+                        ui.label("c:");
+                        ui.add(egui::Slider::new(&mut self.c, (1.)..=30.));
+                        ui.label("N/m");
+                    });
                 });
-            });
 
-            self.forces.display_ui(ui);
-        });
+                self.forces.display_ui(ui);
+            });
     }
 
     fn get_type(&self) -> RigidBodyType {
@@ -162,24 +154,71 @@ impl RigidBody for RigidSpring {
     fn get_radius(&self) -> Option<f32> {
         None
     }
+    fn get_sleeping(&self) -> bool {
+        self.sleeping
+    }
+    fn get_bullet(&self) -> bool {
+        false
+    }
+    fn get_sleep_timer(&self) -> f32 {
+        self.sleep_timer
+    }
+    fn get_spring_energy(&self) -> f32 {
+        // E = 1/2 * c * u^2
+        let u = self.equilibrium - self.pos.y;
+        0.5 * self.c * u * u
+    }
+    fn get_forces(&self) -> Forces {
+        self.forces
+    }
+    fn set_sleeping(&mut self, sleeping: bool) {
+        self.sleeping = sleeping;
+    }
+    fn get_collision_filter(&self) -> CollisionFilter {
+        CollisionFilter::default()
+    }
+    fn set_collision_filter(&mut self, _filter: CollisionFilter) {}
+    fn get_sensor(&self) -> bool {
+        false
+    }
+    fn set_sensor(&mut self, _sensor: bool) {}
+    fn set_bullet(&mut self, _bullet: bool) {}
+    fn set_sleep_timer(&mut self, sleep_timer: f32) {
+        self.sleep_timer = sleep_timer;
+    }
+    fn set_restitution(&mut self, _restitution: f32) {}
+    fn scale(&mut self, factor: Vec2) {
+        self.size *= factor;
+    }
+    fn rotate(&mut self, _angle: f32) {}
+    fn translate(&mut self, offset: Vec2) {
+        self.pos += offset;
+        self.equilibrium += offset.y;
+    }
+    fn duplicate(&self) -> Box<dyn RigidBody> {
+        Box::new(*self)
+    }
+    fn get_state(&self) -> BodyState {
+        BodyState::Spring(*self)
+    }
     fn get_restitution(&self) -> Option<f32> {
         None
     }
     fn set_vel(&mut self, new_vel: Vec2) {
-        self.vel = new_vel;
+        // It only moves up and down
+        self.vel = vec2(0., new_vel.y);
     }
     fn set_pos(&mut self, new_pos: Vec2) {
         self.pos = new_pos;
     }
+    fn get_vertices(&self) -> Option<Vec<Vec2>> {
+        None
+    }
     fn get_size(&self) -> Option<Vec2> {
         Some(self.size)
     }
-    fn as_trait(&self) -> &dyn RigidBody {
-        self as &dyn RigidBody
-    }
-}
-impl RigidSpring {
-    fn contains(&self, point: Vec2) -> bool {
+    // Only the weight at the end of the spring can be grabbed
+    fn contains_point(&self, point: Vec2) -> bool {
         point.x > self.pos.x
             && point.x < self.pos.x + self.size.x
             && point.y < self.pos.y
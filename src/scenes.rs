@@ -1,151 +1,758 @@
-use std::ops::RangeInclusive;
+use std::{
+    f32::consts::TAU,
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
 
+use egui_macroquad::egui::{self, Ui};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    engine::Variables, rigid_body::RigidBody, rigid_circle::RigidCircle,
-    rigid_rectangle::RigidSquare, rigid_spring::RigidSpring, SCREEN_SIZE, SCREEN_X_INCREASE,
+    camera::Camera,
+    collision::CollisionFilter,
+    engine::Variables,
+    fluid::{get_polygon_area, FluidVolume},
+    force_field::{draw_field_arrows, ForceField},
+    rigid_body::{RigidBody, RigidBodyType},
+    rigid_circle::RigidCircle,
+    rigid_polygon::RigidPolygon,
+    rigid_rectangle::RigidSquare,
+    rigid_spring::RigidSpring,
+    sph::{SphFluid, SphSettings},
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Scene {
-    FallingRectangles,
-    BouncingCircles,
-    RectAndCircle,
-    Spring,
+// Scene files in this directory are loaded after the built in scenes
+const SCENE_DIR: &str = "scenes";
+const SCENE_EXTENSION: &str = "ron";
+// Compiled in, so they are there even when the program isn't started from the repository.
+// A file with the same name in SCENE_DIR replaces the built in scene
+const BUILT_IN_SCENES: [(&str, &str); 9] = [
+    (
+        "falling_rectangles",
+        include_str!("../scenes/falling_rectangles.ron"),
+    ),
+    (
+        "bouncing_circles",
+        include_str!("../scenes/bouncing_circles.ron"),
+    ),
+    (
+        "rect_and_circle",
+        include_str!("../scenes/rect_and_circle.ron"),
+    ),
+    ("spring", include_str!("../scenes/spring.ron")),
+    ("buoyancy", include_str!("../scenes/buoyancy.ron")),
+    ("dam_break", include_str!("../scenes/dam_break.ron")),
+    ("bullet", include_str!("../scenes/bullet.ron")),
+    ("filtering", include_str!("../scenes/filtering.ron")),
+    ("goal", include_str!("../scenes/goal.ron")),
+];
+
+// Grid lines closer together than this many pixels are skipped
+const MIN_GRID_SPACING: f32 = 8.;
+
+// Everything needed to start a scene. Scenes are loaded from RON files, see the scenes directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scene {
+    // Name of the file without the extension
+    #[serde(skip)]
+    file_name: String,
+    // Shown in the scene picker, the file name is used when it's left out
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    description: String,
+    // Scenes in the same category are listed together
+    #[serde(default)]
+    category: String,
+    // The world is surrounded by walls, with a floor along the bottom metre
+    world_size: Vec2,
+    variables: SceneVariables,
+    #[serde(default)]
+    bodies: Vec<BodyDescription>,
+    // Pairs of bodies, by their index in bodies, that never collide with each other
+    #[serde(default)]
+    ignored_pairs: Vec<(usize, usize)>,
+    #[serde(default)]
+    sph: Option<SphBlock>,
 }
-impl Scene {
-    pub fn get_world_size(&self) -> Vec2 {
-        match self {
-            Scene::FallingRectangles => vec2(60. * SCREEN_X_INCREASE, 60.),
-            Scene::BouncingCircles => vec2(40. * SCREEN_X_INCREASE, 40.),
-            Scene::RectAndCircle => vec2(60. * SCREEN_X_INCREASE, 60.),
-            Scene::Spring => vec2(40. * SCREEN_X_INCREASE, 40.),
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SceneVariables {
+    g: Option<f32>,
+    rho: Option<f32>,
+    mu: Option<f32>,
+    // Range of the ρ slider and the value its reset button goes back to
+    rho_range: (f32, f32),
+    rho_default: f32,
+    #[serde(default)]
+    fluid: Option<FluidVolume>,
+    // Whether the default force fields can be turned on from the ui
+    force_fields: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BodyShape {
+    Circle { radius: f32 },
+    // Positioned by its top left corner
+    Rectangle { size: Vec2 },
+    // Corners relative to the position, they have to form a convex polygon going counter clockwise
+    Polygon { vertices: Vec<Vec2> },
+    RegularPolygon { radius: f32, sides: usize },
+    Spring { size: Vec2 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyDescription {
+    shape: BodyShape,
+    mass: f32,
+    pos: Vec2,
+    #[serde(default)]
+    vel: Vec2,
+    // Left out to use the default of the body type
+    #[serde(default)]
+    restitution: Option<f32>,
+    #[serde(default)]
+    bullet: bool,
+    #[serde(default)]
+    sensor: bool,
+    #[serde(default)]
+    collision_filter: CollisionFilter,
+}
+impl BodyDescription {
+    // Describes the body the way it is now, so the scene starts with it like this
+    pub fn from_body(rb: &dyn RigidBody) -> Self {
+        let shape = match rb.get_type() {
+            RigidBodyType::Circle => BodyShape::Circle {
+                radius: rb.get_radius().expect("circles have a radius"),
+            },
+            RigidBodyType::Square => BodyShape::Rectangle {
+                size: rb.get_size().expect("rectangles have a size"),
+            },
+            RigidBodyType::Polygon => BodyShape::Polygon {
+                vertices: rb.get_vertices().expect("polygons have vertices"),
+            },
+            RigidBodyType::Spring => BodyShape::Spring {
+                size: rb.get_size().expect("springs have a size"),
+            },
+        };
+        Self {
+            shape,
+            mass: rb.get_mass(),
+            pos: rb.get_pos(),
+            vel: rb.get_vel(),
+            restitution: rb.get_restitution(),
+            bullet: rb.get_bullet(),
+            sensor: rb.get_sensor(),
+            collision_filter: rb.get_collision_filter(),
         }
     }
-    pub fn get_rigid_bodies(&self) -> Vec<Box<dyn RigidBody>> {
-        let world_size = self.get_world_size();
-        match self {
-            Scene::FallingRectangles => {
-                let pos0 = vec2(world_size.x * 0.45, world_size.y * 0.5);
-                let pos1 = vec2(world_size.x * 0.55, world_size.y * 0.5);
-
-                let rs0 = RigidSquare::new(10., pos0, vec2(2., 2.));
-                let rs1 = RigidSquare::new(100., pos1, vec2(2., 2.));
-                vec![Box::new(rs0), Box::new(rs1)]
+
+    fn create_body(&self) -> Box<dyn RigidBody> {
+        let mut rb: Box<dyn RigidBody> = match &self.shape {
+            BodyShape::Circle { radius } => {
+                Box::new(RigidCircle::new(self.mass, self.pos, *radius))
             }
-            Scene::BouncingCircles => {
-                let radius_0 = 1.;
-                let radius_1 = 2.;
-                let radius_2 = 3.;
-                let radius_3 = 4.;
-                let pos0 = vec2(world_size.x * 0.1, world_size.y * 0.8 + radius_0);
-                let pos1 = vec2(world_size.x * 0.3, world_size.y * 0.8 + radius_1);
-                let pos2 = vec2(world_size.x * 0.6, world_size.y * 0.8 + radius_2);
-                let pos3 = vec2(world_size.x * 0.9, world_size.y * 0.8 + radius_3);
-
-                let rb0 = RigidCircle::new(1., pos0, radius_0);
-                let rb1 = RigidCircle::new(4., pos1, radius_1);
-                let rb2 = RigidCircle::new(9., pos2, radius_2);
-                let rb3 = RigidCircle::new(16., pos3, radius_3);
-                vec![Box::new(rb0), Box::new(rb1), Box::new(rb2), Box::new(rb3)]
+            BodyShape::Rectangle { size } => Box::new(RigidSquare::new(self.mass, self.pos, *size)),
+            BodyShape::Polygon { vertices } => {
+                Box::new(RigidPolygon::new(self.mass, self.pos, vertices.clone()))
             }
-            Scene::RectAndCircle => {
-                let size_rs = vec2(6., 6.);
-                let pos_rs = vec2(world_size.x * 0.5 - size_rs.x * 0.5, world_size.y * 0.5);
-
-                let rs0 = RigidSquare::new(12., pos_rs, size_rs);
-                let radius_0 = 1.;
-                let radius_1 = 2.;
-                let radius_2 = 3.;
-                let radius_3 = 4.;
-                let pos0 = vec2(world_size.x * 0.1, world_size.y * 0.8 + radius_0);
-                let pos1 = vec2(world_size.x * 0.3, world_size.y * 0.8 + radius_1);
-                let pos2 = vec2(world_size.x * 0.6, world_size.y * 0.8 + radius_2);
-                let pos3 = vec2(world_size.x * 0.9, world_size.y * 0.8 + radius_3);
-
-                let rb0 = RigidCircle::new(1., pos0, radius_0);
-                let rb1 = RigidCircle::new(4., pos1, radius_1);
-                let rb2 = RigidCircle::new(9., pos2, radius_2);
-                let rb3 = RigidCircle::new(16., pos3, radius_3);
-                vec![
-                    Box::new(rs0),
-                    Box::new(rb0),
-                    Box::new(rb1),
-                    Box::new(rb2),
-                    Box::new(rb3),
-                ]
+            BodyShape::RegularPolygon { radius, sides } => Box::new(RigidPolygon::new_regular(
+                self.mass, self.pos, *radius, *sides,
+            )),
+            BodyShape::Spring { size } => Box::new(RigidSpring::new(self.mass, self.pos, *size)),
+        };
+        rb.set_vel(self.vel);
+        if let Some(restitution) = self.restitution {
+            rb.set_restitution(restitution);
+        }
+        rb.set_bullet(self.bullet);
+        rb.set_sensor(self.sensor);
+        rb.set_collision_filter(self.collision_filter);
+        rb
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        check_finite("mass", &[self.mass])?;
+        check_finite("position", &self.pos.to_array())?;
+        check_finite("velocity", &self.vel.to_array())?;
+        if let Some(restitution) = self.restitution {
+            check_finite("restitution", &[restitution])?;
+        }
+        if self.mass <= 0. {
+            return Err(format!("mass has to be positive, not {}", self.mass));
+        }
+        match &self.shape {
+            BodyShape::Circle { radius } | BodyShape::RegularPolygon { radius, .. } => {
+                check_finite("radius", &[*radius])?;
+                if *radius <= 0. {
+                    return Err(format!("radius has to be positive, not {radius}"));
+                }
             }
-            Scene::Spring => {
-                let pos = vec2(world_size.x * 0.5 - 10., world_size.y * 0.5);
-                let spring = RigidSpring::new(1., pos, vec2(30., 3.));
-                vec![Box::new(spring)]
+            BodyShape::Rectangle { size } | BodyShape::Spring { size } => {
+                check_finite("size", &size.to_array())?;
+                if size.min_element() <= 0. {
+                    return Err(format!("size has to be positive, not {size}"));
+                }
+            }
+            BodyShape::Polygon { vertices } => check_polygon(vertices)?,
+        }
+        match &self.shape {
+            BodyShape::RegularPolygon { sides, .. } if *sides < 3 => {
+                Err("polygons need at least 3 sides".to_owned())
             }
+            _ => Ok(()),
         }
     }
-    pub fn get_next_scene(&self) -> Self {
-        match self {
-            Scene::FallingRectangles => Scene::BouncingCircles,
-            Scene::BouncingCircles => Scene::Spring,
-            Scene::RectAndCircle => Scene::Spring,
-            Scene::Spring => Scene::FallingRectangles,
+}
+
+// Fills the rectangle between `min` and `max` with SPH particles
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SphBlock {
+    settings: SphSettings,
+    min: Vec2,
+    max: Vec2,
+}
+impl SphBlock {
+    fn validate(&self) -> Result<(), String> {
+        let settings = &self.settings;
+        check_finite(
+            "SPH settings",
+            &[
+                settings.rest_density,
+                settings.stiffness,
+                settings.viscosity,
+                settings.particle_radius,
+            ],
+        )?;
+        check_finite(
+            "SPH block",
+            &[self.min.x, self.min.y, self.max.x, self.max.y],
+        )?;
+        // The mass of the particles comes from it, and the density gets divided by that
+        if settings.rest_density <= 0. {
+            return Err(format!(
+                "SPH rest density has to be positive, not {}",
+                settings.rest_density
+            ));
+        }
+        // Filling the block would never end otherwise
+        if settings.particle_radius <= 0. {
+            return Err(format!(
+                "SPH particle radius has to be positive, not {}",
+                settings.particle_radius
+            ));
         }
+        if self.min.x >= self.max.x || self.min.y >= self.max.y {
+            return Err(format!(
+                "SPH block min {} has to be below and left of max {}",
+                self.min, self.max
+            ));
+        }
+        Ok(())
     }
-    pub fn get_variables(&self) -> Variables {
-        match self {
-            Scene::FallingRectangles => Variables::new(Some(0.), Some(1.)),
-            Scene::BouncingCircles => Variables::new(Some(9.81), Some(0.)),
-            Scene::RectAndCircle => Variables::new(Some(9.81), Some(0.)),
-            Scene::Spring => Variables::new(None, None),
+}
+
+// NaN or infinity would spread to every body it touches
+fn check_finite(name: &str, values: &[f32]) -> Result<(), String> {
+    match values.iter().find(|value| !value.is_finite()) {
+        Some(value) => Err(format!("{name} has to be a finite number, not {value}")),
+        None => Ok(()),
+    }
+}
+
+// Collisions and buoyancy only work for convex polygons going counter clockwise, and buoyancy
+// divides by the area
+fn check_polygon(vertices: &[Vec2]) -> Result<(), String> {
+    if vertices.len() < 3 {
+        return Err("polygons need at least 3 vertices".to_owned());
+    }
+    for vertex in vertices {
+        check_finite("vertex", &vertex.to_array())?;
+    }
+    let area = get_polygon_area(vertices);
+    if area <= 0. {
+        return Err(format!(
+            "polygon vertices have to go counter clockwise around some area, the area is {area}"
+        ));
+    }
+    let edges: Vec<Vec2> = (0..vertices.len())
+        .map(|i| vertices[(i + 1) % vertices.len()] - vertices[i])
+        .collect();
+    let mut turned = 0.;
+    for (i, &edge) in edges.iter().enumerate() {
+        let next = edges[(i + 1) % edges.len()];
+        if edge == Vec2::ZERO {
+            return Err(format!(
+                "polygon vertex {i} is in the same place as the next one"
+            ));
+        }
+        if edge.perp_dot(next) < 0. {
+            return Err(format!(
+                "polygon isn't convex at vertex {}",
+                (i + 1) % vertices.len()
+            ));
+        }
+        turned += edge.angle_between(next);
+    }
+    // The edges of a star turn left everywhere, but go around more than once
+    if turned > TAU + 0.01 {
+        return Err("polygon edges go around more than once".to_owned());
+    }
+    Ok(())
+}
+
+impl Scene {
+    // Parses a scene file, `file_name` is used to tell the scenes apart
+    pub fn from_ron(file_name: &str, ron: &str) -> Result<Self, String> {
+        let mut scene: Scene = ron::from_str(ron).map_err(|err| format!("{file_name}: {err}"))?;
+        scene.file_name = file_name.to_owned();
+        if scene.name.is_empty() {
+            scene.name = file_name.to_owned();
+        }
+        scene
+            .validate()
+            .map_err(|err| format!("{file_name}: {err}"))?;
+        Ok(scene)
+    }
+
+    // Catches mistakes that would otherwise crash the engine when the scene gets started
+    fn validate(&self) -> Result<(), String> {
+        check_finite("world size", &self.world_size.to_array())?;
+        let vars = &self.variables;
+        let (rho_min, rho_max) = vars.rho_range;
+        check_finite("ρ range", &[rho_min, rho_max, vars.rho_default])?;
+        for value in [vars.g, vars.rho, vars.mu].into_iter().flatten() {
+            check_finite("variables", &[value])?;
+        }
+        if self.world_size.x <= 0. || self.world_size.y <= 1. {
+            return Err(format!("world size {} is too small", self.world_size));
+        }
+        if rho_min < 0. {
+            return Err(format!("ρ can't go below 0, not {rho_min}"));
+        }
+        // The slider needs a range to slide over
+        if rho_min >= rho_max {
+            return Err(format!(
+                "ρ range has to go from low to high, not from {rho_min} to {rho_max}"
+            ));
+        }
+        if let Some(fluid) = &vars.fluid {
+            check_finite(
+                "fluid",
+                &[
+                    fluid.pos.x,
+                    fluid.pos.y,
+                    fluid.size.x,
+                    fluid.size.y,
+                    fluid.surface_level,
+                    fluid.density,
+                    fluid.viscosity,
+                ],
+            )?;
+            if fluid.size.min_element() <= 0. {
+                return Err(format!("fluid size has to be positive, not {}", fluid.size));
+            }
+        }
+        for (i, body) in self.bodies.iter().enumerate() {
+            body.validate().map_err(|err| format!("body {i}: {err}"))?;
+        }
+        if let Some(sph) = &self.sph {
+            sph.validate()?;
+        }
+        let body_count = self.bodies.len();
+        if let Some(&(a, b)) = self
+            .ignored_pairs
+            .iter()
+            .find(|&&(a, b)| a >= body_count || b >= body_count)
+        {
+            return Err(format!(
+                "ignored pair ({a}, {b}) refers to a body that doesn't exist"
+            ));
         }
+        Ok(())
     }
-    pub fn get_c_range(&self) -> RangeInclusive<f32> {
-        match self {
-            Scene::FallingRectangles => (-1.)..=30.,
-            Scene::BouncingCircles => (-0.01)..=1.,
-            Scene::RectAndCircle => (-0.01)..=1.,
-            Scene::Spring => (-0.01)..=1.,
+
+    // A copy of this scene with other bodies and variables, to be saved as `file_name`
+    pub fn with_changes(
+        &self,
+        file_name: &str,
+        bodies: Vec<BodyDescription>,
+        ignored_pairs: Vec<(usize, usize)>,
+        vars: &Variables,
+    ) -> Scene {
+        let mut scene = self.clone();
+        // A copy saved under another name shouldn't look the same as the original in the picker
+        if file_name != self.file_name {
+            scene.name = file_name.to_owned();
+        }
+        scene.file_name = file_name.to_owned();
+        scene.bodies = bodies;
+        scene.ignored_pairs = ignored_pairs;
+        scene.variables.g = vars.g;
+        scene.variables.rho = vars.rho;
+        scene.variables.mu = vars.mu;
+        scene.variables.fluid = vars.fluid;
+        scene
+    }
+
+    // Writes the scene to the scenes directory, replacing the file with the same name
+    pub fn save(&self) -> Result<PathBuf, String> {
+        if self.file_name.is_empty() || self.file_name.contains(['/', '\\', '.']) {
+            return Err(format!(
+                "\"{}\" can't be used as a file name",
+                self.file_name
+            ));
         }
+        self.validate()?;
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        let path = Path::new(SCENE_DIR).join(format!("{}.{SCENE_EXTENSION}", self.file_name));
+        fs::create_dir_all(SCENE_DIR)
+            .and_then(|_| fs::write(&path, ron))
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(path)
     }
-    pub fn get_c_default(&self) -> f32 {
-        match self {
-            Scene::FallingRectangles => 1.,
-            Scene::BouncingCircles => 0.01,
-            Scene::RectAndCircle => 0.01,
-            Scene::Spring => 0.01,
+
+    pub fn get_file_name(&self) -> &str {
+        &self.file_name
+    }
+    pub fn set_file_name(&mut self, file_name: &str) {
+        self.file_name = file_name.to_owned();
+    }
+    pub fn get_world_size(&self) -> Vec2 {
+        self.world_size
+    }
+    pub fn get_rigid_bodies(&self) -> Vec<Box<dyn RigidBody>> {
+        self.bodies.iter().map(|body| body.create_body()).collect()
+    }
+    pub fn get_variables(&self) -> Variables {
+        let vars = &self.variables;
+        let fields = if vars.force_fields {
+            ForceField::get_defaults(self.world_size)
+        } else {
+            Vec::new()
+        };
+        Variables {
+            fluid: vars.fluid,
+            fields,
+            ..Variables::new(vars.g, vars.rho, vars.mu)
         }
     }
-    pub fn draw_background(&self) {
+    pub fn get_rho_range(&self) -> RangeInclusive<f32> {
+        let (min, max) = self.variables.rho_range;
+        min..=max
+    }
+    pub fn get_rho_default(&self) -> f32 {
+        self.variables.rho_default
+    }
+    pub fn get_sph_fluid(&self) -> Option<SphFluid> {
+        self.sph
+            .as_ref()
+            .map(|block| SphFluid::new_block(block.settings, block.min, block.max))
+    }
+    // Pairs of bodies, by their index in get_rigid_bodies, that never collide with each other
+    pub fn get_ignored_pairs(&self) -> Vec<(usize, usize)> {
+        self.ignored_pairs.clone()
+    }
+    pub fn draw_background(&self, camera: &Camera, fields: &[ForceField]) {
         let world_size = self.get_world_size();
-        let metre_in_pixels = SCREEN_SIZE / world_size;
-
-        for x in 0..=(world_size.x as usize) {
-            draw_line(
-                x as f32 * metre_in_pixels.x,
-                0.,
-                x as f32 * metre_in_pixels.x,
-                SCREEN_SIZE.y,
-                0.5,
-                BLACK,
-            )
-        }
-        for y in 0..=(world_size.y as usize) {
-            draw_line(
-                0.,
-                SCREEN_SIZE.y - y as f32 * metre_in_pixels.y,
-                SCREEN_SIZE.x,
-                SCREEN_SIZE.y - y as f32 * metre_in_pixels.y,
-                0.5,
-                BLACK,
-            )
-        }
-        draw_line(
-            0.,
-            SCREEN_SIZE.y,
-            SCREEN_SIZE.x,
-            SCREEN_SIZE.y,
-            metre_in_pixels.y * 2.,
+        let top_left = camera.world_to_screen(vec2(0., world_size.y));
+        let bottom_right = camera.world_to_screen(vec2(world_size.x, 0.));
+
+        // Only the lines on the screen, and fewer of them when zoomed out
+        let mut spacing = 1.;
+        while camera.metres_to_pixels(spacing) < MIN_GRID_SPACING {
+            spacing *= 2.;
+        }
+        let (visible_min, visible_max) = camera.get_visible_area();
+        let min = visible_min.max(Vec2::ZERO);
+        let max = visible_max.min(world_size);
+
+        for x in get_grid_lines(min.x, max.x, spacing) {
+            let screen_x = camera.world_to_screen(vec2(x, 0.)).x;
+            draw_line(screen_x, top_left.y, screen_x, bottom_right.y, 0.5, BLACK)
+        }
+        for y in get_grid_lines(min.y, max.y, spacing) {
+            let screen_y = camera.world_to_screen(vec2(0., y)).y;
+            draw_line(top_left.x, screen_y, bottom_right.x, screen_y, 0.5, BLACK)
+        }
+        // The window can have a different shape than the world, so show where it ends
+        draw_rectangle_lines(
+            top_left.x,
+            top_left.y,
+            bottom_right.x - top_left.x,
+            bottom_right.y - top_left.y,
+            2.,
+            DARKGRAY,
+        );
+        // The floor is the bottom metre of the world
+        let floor = camera.world_to_screen(vec2(0., 1.));
+        draw_rectangle(
+            floor.x,
+            floor.y,
+            camera.metres_to_pixels(world_size.x),
+            camera.metres_to_pixels(1.),
             BROWN,
-        )
+        );
+
+        draw_field_arrows(fields, world_size, 4, camera);
+    }
+}
+
+// Every scene that can be started, the built in ones first
+#[derive(Debug, Clone, Default)]
+pub struct SceneList {
+    scenes: Vec<Scene>,
+    // Scene files that couldn't be loaded
+    errors: Vec<String>,
+}
+impl SceneList {
+    pub fn load() -> Self {
+        let mut list = Self::default();
+        for (file_name, ron) in BUILT_IN_SCENES {
+            let scene = Scene::from_ron(file_name, ron).unwrap_or_else(|err| panic!("{err}"));
+            list.scenes.push(scene);
+        }
+        list.load_dir(Path::new(SCENE_DIR));
+        list
+    }
+
+    pub fn get(&self, file_name: &str) -> Option<Scene> {
+        self.scenes
+            .iter()
+            .find(|scene| scene.file_name == file_name)
+            .cloned()
+    }
+
+    // Loads everything again, to pick up files that changed
+    pub fn reload(&mut self) {
+        *self = SceneList::load();
+    }
+
+    fn load_dir(&mut self, dir: &Path) {
+        // Without a scene directory there are still the built in scenes
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == SCENE_EXTENSION))
+            .collect();
+        // The directory isn't listed in any particular order
+        paths.sort();
+
+        for path in paths {
+            let file_name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let scene = fs::read_to_string(&path)
+                .map_err(|err| format!("{file_name}: {err}"))
+                .and_then(|ron| Scene::from_ron(&file_name, &ron));
+            match scene {
+                Ok(scene) => self.insert(scene),
+                Err(err) => self.errors.push(err),
+            }
+        }
+    }
+
+    // Replaces the scene with the same file name, if there is one
+    fn insert(&mut self, scene: Scene) {
+        match self
+            .scenes
+            .iter_mut()
+            .find(|other| other.file_name == scene.file_name)
+        {
+            Some(other) => *other = scene,
+            None => self.scenes.push(scene),
+        }
+    }
+
+    pub fn get_first(&self) -> Scene {
+        self.scenes[0].clone()
+    }
+
+    // Returns the scene that was picked
+    pub fn update_ui(&mut self, ui: &mut Ui, current: &Scene) -> Option<Scene> {
+        let mut picked = None;
+        // In the order they first show up in, so the built in categories come first
+        let mut categories: Vec<&str> = Vec::new();
+        for scene in self.scenes.iter() {
+            if !categories.contains(&scene.category.as_str()) {
+                categories.push(&scene.category);
+            }
+        }
+
+        egui::ComboBox::from_label("Scene")
+            .selected_text(&current.name)
+            .show_ui(ui, |ui| {
+                for category in categories {
+                    if !category.is_empty() {
+                        ui.label(egui::RichText::new(category).strong());
+                    }
+                    for scene in self
+                        .scenes
+                        .iter()
+                        .filter(|scene| scene.category == category)
+                    {
+                        let is_current = scene.file_name == current.file_name;
+                        let mut response = ui.selectable_label(is_current, &scene.name);
+                        if !scene.description.is_empty() {
+                            response = response.on_hover_text(&scene.description);
+                        }
+                        if response.clicked() {
+                            picked = Some(scene.clone());
+                        }
+                    }
+                }
+            });
+        if !current.description.is_empty() {
+            ui.label(&current.description);
+        }
+
+        for err in self.errors.iter() {
+            ui.colored_label(egui::Color32::RED, err);
+        }
+        if ui
+            .button("Reload scene files")
+            .on_hover_text(format!(
+                "Scenes are loaded from .{SCENE_EXTENSION} files in the \"{SCENE_DIR}\" directory"
+            ))
+            .clicked()
+        {
+            self.reload();
+        }
+        picked
+    }
+}
+
+// Multiples of `spacing` between `min` and `max`
+fn get_grid_lines(min: f32, max: f32, spacing: f32) -> impl Iterator<Item = f32> {
+    let first = (min / spacing).ceil() as i32;
+    let last = (max / spacing).floor() as i32;
+    (first..=last).map(move |i| i as f32 * spacing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Uses everything that gets validated, the tests change one thing at a time
+    const VALID_SCENE: &str = "(
+        world_size: (20.0, 20.0),
+        variables: (
+            g: Some(9.81),
+            rho: Some(0.0),
+            mu: Some(0.0),
+            rho_range: (0.0, 1.0),
+            rho_default: 0.0,
+            fluid: Some((
+                pos: (0.0, 10.0),
+                size: (20.0, 9.0),
+                surface_level: 5.0,
+                density: 10.0,
+                viscosity: 1.0,
+            )),
+            force_fields: true,
+        ),
+        bodies: [
+            (shape: Circle(radius: 1.0), mass: 1.0, pos: (5.0, 15.0)),
+            (shape: Polygon(vertices: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]), mass: 1.0, pos: (9.0, 15.0)),
+        ],
+        ignored_pairs: [(0, 1)],
+        sph: Some((
+            settings: (rest_density: 10.0, stiffness: 1000.0, viscosity: 10.0, particle_radius: 0.25),
+            min: (1.0, 1.0),
+            max: (3.0, 3.0),
+        )),
+    )";
+
+    fn load_changed(from: &str, to: &str) -> Result<Scene, String> {
+        assert!(VALID_SCENE.contains(from), "{from} isn't in the scene");
+        Scene::from_ron("test", &VALID_SCENE.replacen(from, to, 1))
+    }
+
+    fn assert_rejected(from: &str, to: &str) {
+        assert!(
+            load_changed(from, to).is_err(),
+            "the scene loaded with {to} instead of {from}"
+        );
+    }
+
+    #[test]
+    fn built_in_scenes_are_valid() {
+        for (file_name, ron) in BUILT_IN_SCENES {
+            if let Err(err) = Scene::from_ron(file_name, ron) {
+                panic!("{err}");
+            }
+        }
+        Scene::from_ron("test", VALID_SCENE).unwrap();
+    }
+
+    #[test]
+    fn bodies_need_a_positive_mass_and_size() {
+        assert_rejected("mass: 1.0", "mass: 0.0");
+        assert_rejected("Circle(radius: 1.0)", "Circle(radius: -1.0)");
+        assert_rejected("Circle(radius: 1.0)", "Rectangle(size: (2.0, 0.0))");
+        assert_rejected(
+            "Circle(radius: 1.0)",
+            "RegularPolygon(radius: 1.0, sides: 2)",
+        );
+    }
+
+    #[test]
+    fn numbers_have_to_be_finite() {
+        assert_rejected("pos: (5.0, 15.0)", "pos: (NaN, 15.0)");
+        assert_rejected("mass: 1.0", "mass: inf");
+        assert_rejected("g: Some(9.81)", "g: Some(NaN)");
+        assert_rejected("surface_level: 5.0", "surface_level: NaN");
+        assert_rejected("stiffness: 1000.0", "stiffness: inf");
+    }
+
+    #[test]
+    fn polygons_have_to_be_convex_and_counter_clockwise() {
+        let triangle = "[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]";
+        assert_rejected(triangle, "[(0.0, 0.0), (0.0, 1.0), (1.0, 0.0)]");
+        assert_rejected(triangle, "[(0.0, 0.0), (1.0, 0.0), (2.0, 0.0)]");
+        assert_rejected(triangle, "[(0.0, 0.0), (1.0, 0.0)]");
+        assert_rejected(triangle, "[(0.0, 0.0), (1.0, 0.0), (1.0, 0.0), (0.0, 1.0)]");
+        // An arrow head pointing up
+        assert_rejected(triangle, "[(0.0, 0.0), (1.0, 0.5), (2.0, 0.0), (1.0, 2.0)]");
+        // A five pointed star, every corner turns left but it goes around twice
+        assert_rejected(
+            triangle,
+            "[(0.0, 1.0), (-0.59, -0.81), (0.95, 0.31), (-0.95, 0.31), (0.59, -0.81)]",
+        );
+        load_changed(triangle, "[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]").unwrap();
+    }
+
+    #[test]
+    fn fluids_need_room() {
+        assert_rejected("size: (20.0, 9.0)", "size: (20.0, 0.0)");
+        assert_rejected("rest_density: 10.0", "rest_density: 0.0");
+        assert_rejected("particle_radius: 0.25", "particle_radius: 0.0");
+        assert_rejected("max: (3.0, 3.0)", "max: (1.0, 3.0)");
+    }
+
+    #[test]
+    fn rho_range_has_to_go_up_from_zero() {
+        assert_rejected("rho_range: (0.0, 1.0)", "rho_range: (-1.0, 1.0)");
+        assert_rejected("rho_range: (0.0, 1.0)", "rho_range: (1.0, 1.0)");
+        assert_rejected("rho_range: (0.0, 1.0)", "rho_range: (1.0, 0.5)");
+    }
+
+    #[test]
+    fn ignored_pairs_have_to_refer_to_bodies() {
+        assert_rejected("ignored_pairs: [(0, 1)]", "ignored_pairs: [(0, 2)]");
+    }
+
+    #[test]
+    fn world_has_to_be_bigger_than_the_floor() {
+        assert_rejected("world_size: (20.0, 20.0)", "world_size: (20.0, 1.0)");
+        assert_rejected("world_size: (20.0, 20.0)", "world_size: (0.0, 20.0)");
     }
 }
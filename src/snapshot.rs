@@ -0,0 +1,47 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body_set::{BodyHandle, BodySet},
+    contact_solver::ContactSolver,
+    engine::Variables,
+    scenes::Scene,
+    sph::SphFluid,
+};
+
+// Everything that changes while the simulation runs, so it can be saved to a file and
+// continued exactly where it was. Settings that only change how things are shown aren't in here
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    // The scene itself doesn't save its file name, it is only known once it's loaded
+    pub scene_file_name: String,
+    pub scene: Scene,
+    pub rigid_bodies: BodySet,
+    pub world_size: Vec2,
+    pub vars: Variables,
+    pub sph: Option<SphFluid>,
+    // Also holds the ignored pairs and the impulses used for warm starting
+    pub solver: ContactSolver,
+    pub speed_limit: Option<f32>,
+    pub sensor_enter_counts: BTreeMap<BodyHandle, usize>,
+    pub sleeping_enabled: bool,
+    pub time_mult: f32,
+    pub time_passed: f32,
+}
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let ron = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+        fs::write(path, ron).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let ron = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut snapshot: Snapshot =
+            ron::from_str(&ron).map_err(|err| format!("{}: {err}", path.display()))?;
+        snapshot.scene.set_file_name(&snapshot.scene_file_name);
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+
+// Uniform grid that buckets indices by position, so only nearby things have to be compared
+#[derive(Debug, Clone, Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    // Removes every index but keeps the allocated cells around, optionally changing the cell size
+    pub fn clear(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.cells.values_mut().for_each(|cell| cell.clear());
+    }
+
+    pub fn insert(&mut self, index: usize, pos: Vec2) {
+        let cell = self.get_cell(pos);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    // Indices in the cell of `pos` and the eight cells around it. With a cell size of at least
+    // the search radius this contains everything within that radius.
+    pub fn get_neighbours(&self, pos: Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = self.get_cell(pos);
+        (-1..=1)
+            .flat_map(move |dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    // Puts the index in every cell the box between `min` and `max` touches
+    pub fn insert_box(&mut self, index: usize, min: Vec2, max: Vec2) {
+        let (min_x, min_y) = self.get_cell(min);
+        let (max_x, max_y) = self.get_cell(max);
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    // Indices in every cell the box between `min` and `max` touches. An index comes up once for
+    // every cell it shares with the box
+    pub fn get_in_box(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (min_x, min_y) = self.get_cell(min);
+        let (max_x, max_y) = self.get_cell(max);
+        (min_x..=max_x)
+            .flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    pub fn get_cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    // Cells that have something in them
+    pub fn get_used_cells(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.cells
+            .iter()
+            .filter(|(_, cell)| !cell.is_empty())
+            .map(|(&cell, _)| cell)
+    }
+
+    fn get_cell(&self, pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(indices: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut indices: Vec<usize> = indices.collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    #[test]
+    fn neighbours_are_in_the_same_and_the_surrounding_cells() {
+        let mut grid = SpatialGrid::new(1.);
+        grid.insert(0, vec2(0.5, 0.5));
+        grid.insert(1, vec2(1.5, 1.5));
+        grid.insert(2, vec2(-0.5, -0.5));
+        grid.insert(3, vec2(2.5, 0.5));
+        assert_eq!(sorted(grid.get_neighbours(vec2(0.2, 0.8))), vec![0, 1, 2]);
+        assert_eq!(sorted(grid.get_neighbours(vec2(2.2, 0.2))), vec![1, 3]);
+    }
+
+    #[test]
+    fn clearing_removes_every_index() {
+        let mut grid = SpatialGrid::new(1.);
+        grid.insert(0, vec2(0.5, 0.5));
+        grid.clear(2.);
+        assert_eq!(grid.get_neighbours(vec2(0.5, 0.5)).count(), 0);
+        assert_eq!(grid.get_used_cells().count(), 0);
+        assert_eq!(grid.get_cell_size(), 2.);
+    }
+
+    #[test]
+    fn boxes_are_found_in_every_cell_they_touch() {
+        let mut grid = SpatialGrid::new(2.);
+        grid.insert_box(0, vec2(0.5, 0.5), vec2(4.5, 1.5));
+        grid.insert_box(1, vec2(5., 5.), vec2(5.5, 5.5));
+        assert_eq!(grid.get_used_cells().count(), 4);
+        assert_eq!(
+            sorted(grid.get_in_box(vec2(4.2, 0.), vec2(4.4, 0.2))),
+            vec![0]
+        );
+        assert_eq!(
+            sorted(grid.get_in_box(vec2(5.8, 5.8), vec2(7., 7.))),
+            vec![1]
+        );
+        assert_eq!(grid.get_in_box(vec2(0., 3.), vec2(3., 3.5)).count(), 0);
+        // Once for every cell the two boxes share
+        assert_eq!(
+            sorted(grid.get_in_box(vec2(0., 0.), vec2(5., 1.))),
+            vec![0, 0, 0]
+        );
+    }
+}
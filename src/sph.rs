@@ -0,0 +1,319 @@
+use std::f32::consts::PI;
+
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    body_set::BodySet,
+    camera::Camera,
+    collision::{get_contact, Shape},
+    engine::Variables,
+    force_field::sample_fields,
+    rigid_body::Format,
+    spatial_grid::SpatialGrid,
+};
+
+// SPH is only stable for small time steps, so bigger ones get split up
+const MAX_SUB_STEP: f32 = 0.004;
+// Fraction of the velocity kept when a particle bounces off the edge of the world
+const WALL_RESTITUTION: f32 = 0.3;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SphSettings {
+    pub rest_density: f32,
+    pub stiffness: f32,
+    pub viscosity: f32,
+    pub particle_radius: f32,
+}
+impl SphSettings {
+    // Particles are spawned one diameter apart, the kernel reaches about two particles further
+    fn get_smoothing_radius(&self) -> f32 {
+        self.particle_radius * 4.
+    }
+    // Each particle represents the square of fluid around it at rest density
+    fn get_particle_mass(&self) -> f32 {
+        self.rest_density * (self.particle_radius * 2.).powi(2)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SphParticle {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    density: f32,
+    pressure: f32,
+    acc: Vec2,
+}
+impl SphParticle {
+    pub fn new(pos: Vec2) -> Self {
+        Self {
+            pos,
+            vel: Vec2::ZERO,
+            density: 0.,
+            pressure: 0.,
+            acc: Vec2::ZERO,
+        }
+    }
+}
+
+// Smoothed-particle hydrodynamics liquid, see "Particle-Based Fluid Simulation for Interactive
+// Applications" by Müller et al. for the kernels
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SphFluid {
+    particles: Vec<SphParticle>,
+    settings: SphSettings,
+    default_settings: SphSettings,
+    // Gets rebuilt at the start of every step
+    #[serde(skip)]
+    grid: SpatialGrid,
+}
+impl SphFluid {
+    // Fills the rectangle between `min` and `max` with particles at rest
+    pub fn new_block(settings: SphSettings, min: Vec2, max: Vec2) -> Self {
+        let spacing = settings.particle_radius * 2.;
+        let mut particles = Vec::new();
+        let mut y = min.y + settings.particle_radius;
+        while y < max.y {
+            let mut x = min.x + settings.particle_radius;
+            while x < max.x {
+                particles.push(SphParticle::new(vec2(x, y)));
+                x += spacing;
+            }
+            y += spacing;
+        }
+
+        Self {
+            particles,
+            settings,
+            default_settings: settings,
+            grid: SpatialGrid::new(settings.get_smoothing_radius()),
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        vars: &Variables,
+        delta_time: f32,
+        scene_size: Vec2,
+        rigid_bodies: &mut BodySet,
+    ) {
+        let sub_steps = (delta_time.abs() / MAX_SUB_STEP).ceil().max(1.);
+        let sub_step = delta_time / sub_steps;
+        for _ in 0..sub_steps as usize {
+            self.compute_densities();
+            self.compute_accelerations(vars);
+            self.integrate(sub_step, scene_size);
+            self.interact_with_rigid_bodies(rigid_bodies);
+        }
+    }
+
+    // Used to find the neighbours of the particles
+    pub fn get_grid(&self) -> &SpatialGrid {
+        &self.grid
+    }
+
+    pub fn get_settings(&self) -> SphSettings {
+        self.settings
+    }
+    pub fn set_settings(&mut self, settings: SphSettings) {
+        self.settings = settings;
+    }
+
+    fn compute_densities(&mut self) {
+        let h = self.settings.get_smoothing_radius();
+        let mass = self.settings.get_particle_mass();
+
+        self.grid.clear(h);
+        for (i, particle) in self.particles.iter().enumerate() {
+            self.grid.insert(i, particle.pos);
+        }
+
+        for i in 0..self.particles.len() {
+            let pos = self.particles[i].pos;
+            let density: f32 = self
+                .grid
+                .get_neighbours(pos)
+                .map(|j| mass * poly6(pos.distance_squared(self.particles[j].pos), h))
+                .sum();
+
+            // p = k * (rho - rho_0), clamped so the surface doesn't clump together
+            let pressure = self.settings.stiffness * (density - self.settings.rest_density);
+            self.particles[i].density = density;
+            self.particles[i].pressure = pressure.max(0.);
+        }
+    }
+
+    fn compute_accelerations(&mut self, vars: &Variables) {
+        let h = self.settings.get_smoothing_radius();
+        let mass = self.settings.get_particle_mass();
+
+        for i in 0..self.particles.len() {
+            let p_i = self.particles[i];
+            let mut f_pressure = Vec2::ZERO;
+            let mut f_viscosity = Vec2::ZERO;
+
+            for j in self.grid.get_neighbours(p_i.pos) {
+                if i == j {
+                    continue;
+                }
+                let p_j = self.particles[j];
+                let offset = p_i.pos - p_j.pos;
+                let dist = offset.length();
+                if dist >= h {
+                    continue;
+                }
+                // Particles on the exact same spot still have to be pushed apart
+                let dir = if dist > 0. {
+                    offset / dist
+                } else {
+                    Vec2::from_angle(i as f32)
+                };
+
+                f_pressure += dir * mass * (p_i.pressure + p_j.pressure) / (2. * p_j.density)
+                    * -spiky_gradient(dist, h);
+                f_viscosity += self.settings.viscosity * mass * (p_j.vel - p_i.vel) / p_j.density
+                    * viscosity_laplacian(dist, h);
+            }
+
+            // a = f / rho, because the forces above are per unit of volume
+            let mut acc = (f_pressure + f_viscosity) / p_i.density;
+            if let Some(g) = vars.g {
+                acc.y -= g;
+            }
+            if let Some(field) = sample_fields(&vars.fields, p_i.pos) {
+                acc += field;
+            }
+            self.particles[i].acc = acc;
+        }
+    }
+
+    fn integrate(&mut self, delta_time: f32, scene_size: Vec2) {
+        let r = self.settings.particle_radius;
+        for particle in self.particles.iter_mut() {
+            particle.vel += particle.acc * delta_time;
+            particle.pos += particle.vel * delta_time;
+
+            // The floor is 1 metre high, the same as for the rigidbodies
+            if particle.pos.y < 1. + r {
+                particle.pos.y = 1. + r;
+                particle.vel.y *= -WALL_RESTITUTION;
+            } else if particle.pos.y > scene_size.y - r {
+                particle.pos.y = scene_size.y - r;
+                particle.vel.y *= -WALL_RESTITUTION;
+            }
+            if particle.pos.x < r {
+                particle.pos.x = r;
+                particle.vel.x *= -WALL_RESTITUTION;
+            } else if particle.pos.x > scene_size.x - r {
+                particle.pos.x = scene_size.x - r;
+                particle.vel.x *= -WALL_RESTITUTION;
+            }
+        }
+    }
+
+    // Pushes particles out of rigid circles and rectangles and gives the bodies the opposite impulse
+    fn interact_with_rigid_bodies(&mut self, rigid_bodies: &mut BodySet) {
+        let r = self.settings.particle_radius;
+        let particle_mass = self.settings.get_particle_mass();
+
+        for rb in rigid_bodies.iter_mut() {
+            if !rb.get_enabled() || rb.get_sensor() {
+                continue;
+            }
+            let Some(shape) = rb.get_shape() else {
+                continue;
+            };
+            let inverse_mass = 1. / rb.get_mass();
+            let mut body_vel = rb.get_vel();
+
+            for particle in self.particles.iter_mut() {
+                let particle_shape = Shape::Circle {
+                    center: particle.pos,
+                    radius: r,
+                };
+                let Some(contact) = get_contact(&shape, &particle_shape) else {
+                    continue;
+                };
+                let normal = contact.normal;
+                particle.pos += normal * contact.penetration;
+
+                // Only resolve if the particle and body are moving towards each other
+                let vel_along_normal = (particle.vel - body_vel).dot(normal);
+                if vel_along_normal >= 0. {
+                    continue;
+                }
+                // Inelastic impulse, the fluid doesn't bounce off the body
+                let impulse = -vel_along_normal / (1. / particle_mass + inverse_mass);
+                particle.vel += normal * impulse / particle_mass;
+                body_vel -= normal * impulse * inverse_mass;
+            }
+            rb.set_vel(body_vel);
+        }
+    }
+
+    pub fn draw(&self, camera: &Camera) {
+        for particle in self.particles.iter() {
+            // Compressed particles are drawn darker
+            let compression = (particle.density / self.settings.rest_density - 1.).clamp(0., 1.);
+            let color = Color::new(0.1, 0.5 - 0.3 * compression, 1. - 0.4 * compression, 1.);
+            let pos = camera.world_to_screen(particle.pos);
+            draw_circle(
+                pos.x,
+                pos.y,
+                camera.metres_to_pixels(self.settings.particle_radius),
+                color,
+            );
+        }
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.collapsing("Show SPH settings", |ui| {
+            ui.label(format!("Particles: {}", self.particles.len()));
+            ui.label(format!(
+                "Particle mass: {} kg",
+                self.settings.get_particle_mass().format(2)
+            ));
+            let settings = &mut self.settings;
+            ui.horizontal(|ui| {
+                ui.label("Rest density:")
+                    .on_hover_text("Density the fluid tries to keep in kg/m³");
+                ui.add(egui::Slider::new(&mut settings.rest_density, (1.)..=50.));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Stiffness:")
+                    .on_hover_text("How hard the fluid pushes back when compressed");
+                ui.add(egui::Slider::new(&mut settings.stiffness, (100.)..=5000.));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Viscosity:");
+                ui.add(egui::Slider::new(&mut settings.viscosity, (0.)..=50.));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Particle radius:");
+                ui.add(egui::Slider::new(&mut settings.particle_radius, (0.1)..=1.));
+                ui.label("m");
+            });
+            if ui.button("Reset to default").clicked() {
+                self.settings = self.default_settings;
+            }
+        });
+    }
+}
+
+// 2D kernels, normalised so they integrate to 1 over the circle with radius h
+fn poly6(dist_squared: f32, h: f32) -> f32 {
+    let h_squared = h * h;
+    if dist_squared >= h_squared {
+        return 0.;
+    }
+    4. / (PI * h.powi(8)) * (h_squared - dist_squared).powi(3)
+}
+
+fn spiky_gradient(dist: f32, h: f32) -> f32 {
+    -30. / (PI * h.powi(5)) * (h - dist).powi(2)
+}
+
+fn viscosity_laplacian(dist: f32, h: f32) -> f32 {
+    40. / (PI * h.powi(5)) * (h - dist)
+}
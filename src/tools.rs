@@ -0,0 +1,212 @@
+use std::f32::consts::{PI, TAU};
+
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+
+use crate::{
+    camera::Camera,
+    force_field::draw_arrow,
+    rigid_body::{RigidBody, BODY_DEPTH},
+    rigid_circle::RigidCircle,
+    rigid_polygon::RigidPolygon,
+    rigid_rectangle::RigidSquare,
+};
+
+// Launched bodies get this many m/s for every metre the mouse was dragged
+const LAUNCH_SPEED_PER_METRE: f32 = 3.;
+// Shorter drags count as clicks, which spawn a body of the default size
+const MIN_DRAG: f32 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    Select,
+    Drag,
+    Circle,
+    Rectangle,
+    Polygon,
+}
+
+// What dragging the mouse does while spawning a body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DragMode {
+    Size,
+    Velocity,
+}
+
+// Tools for spawning bodies with the mouse
+pub struct Toolbar {
+    pub tool: Tool,
+    drag_mode: DragMode,
+    // Diameter of circles and polygons, width and height of rectangles
+    size: f32,
+    // Mass per m² of the spawned bodies
+    density: f32,
+    polygon_sides: usize,
+    drag_start: Option<Vec2>,
+    // Settings of the mouse joint used by the drag tool
+    pub joint_frequency: f32,
+    pub joint_damping_ratio: f32,
+}
+impl Toolbar {
+    pub fn new() -> Self {
+        Self {
+            tool: Tool::Select,
+            drag_mode: DragMode::Size,
+            size: 2.,
+            density: 2.,
+            polygon_sides: 5,
+            drag_start: None,
+            joint_frequency: 5.,
+            joint_damping_ratio: 0.7,
+        }
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.tool, Tool::Select, "Select")
+                .on_hover_text("Click a body to select it");
+            ui.selectable_value(&mut self.tool, Tool::Drag, "Drag")
+                .on_hover_text("Pull bodies around with a spring attached to the mouse");
+            ui.selectable_value(&mut self.tool, Tool::Circle, "Circle");
+            ui.selectable_value(&mut self.tool, Tool::Rectangle, "Rect");
+            ui.selectable_value(&mut self.tool, Tool::Polygon, "Polygon");
+        });
+        ui.label("Right click a body to remove it");
+        match self.tool {
+            Tool::Select => return,
+            Tool::Drag => {
+                ui.horizontal(|ui| {
+                    ui.label("Stiffness:")
+                        .on_hover_text("How fast the body follows the mouse");
+                    ui.add(egui::Slider::new(&mut self.joint_frequency, (0.5)..=20.));
+                    ui.label("Hz");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Damping ratio:")
+                        .on_hover_text("At 1 the body stops at the mouse without swinging past it");
+                    ui.add(egui::Slider::new(&mut self.joint_damping_ratio, (0.)..=2.));
+                });
+                return;
+            }
+            Tool::Circle | Tool::Rectangle | Tool::Polygon => {}
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Drag sets:");
+            ui.radio_value(&mut self.drag_mode, DragMode::Size, "size");
+            ui.radio_value(&mut self.drag_mode, DragMode::Velocity, "velocity");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Size:")
+                .on_hover_text("Used when clicking, or when dragging sets the velocity");
+            ui.add(egui::Slider::new(&mut self.size, (0.2)..=10.));
+            ui.label("m");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Density:");
+            ui.add(egui::Slider::new(&mut self.density, (0.1)..=20.));
+            ui.label("kg/m²");
+        });
+        if self.tool == Tool::Polygon {
+            ui.horizontal(|ui| {
+                ui.label("Sides:");
+                ui.add(egui::Slider::new(&mut self.polygon_sides, 3..=10));
+            });
+        }
+    }
+
+    pub fn start_drag(&mut self, mouse_pos: Vec2) {
+        self.drag_start = Some(mouse_pos);
+    }
+
+    // Returns the body dragged out since start_drag, if there was a drag
+    pub fn finish_drag(&mut self, mouse_pos: Vec2) -> Option<Box<dyn RigidBody>> {
+        let start = self.drag_start.take()?;
+        self.create_body(start, mouse_pos)
+    }
+
+    fn create_body(&self, start: Vec2, end: Vec2) -> Option<Box<dyn RigidBody>> {
+        let drag = end - start;
+        let dragged = drag.length() >= MIN_DRAG;
+        let sized_by_drag = dragged && self.drag_mode == DragMode::Size;
+        let vel = if dragged && self.drag_mode == DragMode::Velocity {
+            drag * LAUNCH_SPEED_PER_METRE
+        } else {
+            Vec2::ZERO
+        };
+
+        // Circles and polygons are centred on the start of the drag
+        let radius = if sized_by_drag {
+            drag.length()
+        } else {
+            self.size * 0.5
+        };
+        let mut body: Box<dyn RigidBody> = match self.tool {
+            Tool::Select | Tool::Drag => return None,
+            Tool::Circle => {
+                let mass = self.get_mass(PI * radius * radius);
+                Box::new(RigidCircle::new(mass, start, radius))
+            }
+            Tool::Polygon => {
+                let sides = self.polygon_sides;
+                // Made of one triangle per side, with two sides as long as the radius
+                let area = 0.5 * sides as f32 * radius * radius * (TAU / sides as f32).sin();
+                Box::new(RigidPolygon::new_regular(
+                    self.get_mass(area),
+                    start,
+                    radius,
+                    sides,
+                ))
+            }
+            // Rectangles span from the start of the drag to the mouse
+            Tool::Rectangle => {
+                let (top_left, size) = if sized_by_drag {
+                    (
+                        vec2(start.x.min(end.x), start.y.max(end.y)),
+                        drag.abs().max(Vec2::splat(MIN_DRAG)),
+                    )
+                } else {
+                    (
+                        start + vec2(-self.size, self.size) * 0.5,
+                        Vec2::splat(self.size),
+                    )
+                };
+                Box::new(RigidSquare::new(
+                    self.get_mass(size.x * size.y),
+                    top_left,
+                    size,
+                ))
+            }
+        };
+        body.set_vel(vel);
+        Some(body)
+    }
+
+    fn get_mass(&self, area: f32) -> f32 {
+        (self.density * area * BODY_DEPTH).max(0.1)
+    }
+
+    // The body that will be spawned when the mouse is released at `mouse_pos`
+    pub fn get_preview_body(&self, mouse_pos: Vec2) -> Option<Box<dyn RigidBody>> {
+        self.create_body(self.drag_start?, mouse_pos)
+    }
+
+    // Shows the body that will be spawned when the mouse is released
+    pub fn draw_preview(&self, camera: &Camera) {
+        let Some(start) = self.drag_start else {
+            return;
+        };
+        let mouse_pos = camera.get_mouse_pos();
+        if let Some(body) = self.get_preview_body(mouse_pos) {
+            body.draw(camera);
+        }
+        if self.drag_mode == DragMode::Velocity {
+            draw_arrow(
+                camera.world_to_screen(start),
+                camera.world_to_screen(mouse_pos),
+                2.,
+                DARKGREEN,
+            );
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{HashMap, VecDeque};
+
+use egui_macroquad::egui::{self, Ui};
+use macroquad::prelude::*;
+
+use crate::{
+    body_set::{BodyHandle, BodySet},
+    camera::Camera,
+};
+
+const TRAIL_COLOR: Color = DARKGRAY;
+const PREDICTION_COLOR: Color = ORANGE;
+// The predicted path is drawn as dashes this many points long
+const DASH_LENGTH: usize = 3;
+
+// Lines behind the bodies showing where they were during the last few seconds
+pub struct Trails {
+    pub enabled: bool,
+    // Seconds until a point of the trail fades away
+    duration: f32,
+    time: f32,
+    // (time, centre of mass) after every step
+    points: HashMap<BodyHandle, VecDeque<(f32, Vec2)>>,
+}
+impl Trails {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            duration: 2.,
+            time: 0.,
+            points: HashMap::new(),
+        }
+    }
+
+    // Has to be called right after every step
+    pub fn record_step(&mut self, delta_time: f32, rigid_bodies: &BodySet) {
+        self.time += delta_time;
+        if !self.enabled {
+            self.points.clear();
+            return;
+        }
+        // Removed bodies don't leave their trail behind
+        self.points
+            .retain(|&handle, _| rigid_bodies.get(handle).is_some());
+        for (handle, rb) in rigid_bodies.iter_with_handles() {
+            if rb.get_enabled() {
+                let points = self.points.entry(handle).or_default();
+                points.push_back((self.time, rb.get_center_of_mass()));
+            }
+        }
+        for points in self.points.values_mut() {
+            while points
+                .front()
+                .is_some_and(|(time, _)| *time < self.time - self.duration)
+            {
+                points.pop_front();
+            }
+        }
+    }
+
+    pub fn draw(&self, camera: &Camera) {
+        if !self.enabled {
+            return;
+        }
+        for points in self.points.values() {
+            for (&(time, start), &(_, end)) in points.iter().zip(points.iter().skip(1)) {
+                // Older parts are more transparent
+                let alpha = 1. - (self.time - time) / self.duration;
+                let color = Color::new(TRAIL_COLOR.r, TRAIL_COLOR.g, TRAIL_COLOR.b, alpha);
+                let start = camera.world_to_screen(start);
+                let end = camera.world_to_screen(end);
+                draw_line(start.x, start.y, end.x, end.y, 2., color);
+            }
+        }
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "trails");
+            ui.add(egui::Slider::new(&mut self.duration, (0.2)..=10.));
+            ui.label("s");
+        });
+    }
+}
+
+// Shows where a body is going to go by simulating a copy of the world
+#[derive(Clone)]
+pub struct Prediction {
+    pub enabled: bool,
+    // Seconds to simulate ahead
+    pub duration: f32,
+    // Hash of everything the last path was predicted from, and that path
+    cache: Option<(u64, Option<Vec<Vec2>>)>,
+}
+impl Prediction {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            duration: 3.,
+            cache: None,
+        }
+    }
+
+    // Only calls `predict` when the hash of the input is different from last time
+    pub fn update(&mut self, input: u64, predict: impl FnOnce() -> Option<Vec<Vec2>>) {
+        if self
+            .cache
+            .as_ref()
+            .is_some_and(|(last_input, _)| *last_input == input)
+        {
+            return;
+        }
+        self.cache = Some((input, predict()));
+    }
+
+    pub fn clear(&mut self) {
+        self.cache = None;
+    }
+
+    pub fn get_path(&self) -> Option<&[Vec2]> {
+        self.cache.as_ref()?.1.as_deref()
+    }
+
+    pub fn update_ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "predict").on_hover_text(
+                "Show the path of the body being spawned or dragged, or else the selected body. The fluid is left out",
+            );
+            ui.add(egui::Slider::new(&mut self.duration, (0.5)..=10.));
+            ui.label("s");
+        });
+    }
+}
+
+// Dashed line through the positions of a predicted path
+pub fn draw_predicted_path(path: &[Vec2], camera: &Camera) {
+    let points: Vec<Vec2> = path
+        .iter()
+        .map(|&pos| camera.world_to_screen(pos))
+        .collect();
+    for (i, segment) in points.windows(2).enumerate() {
+        if (i / DASH_LENGTH).is_multiple_of(2) {
+            draw_line(
+                segment[0].x,
+                segment[0].y,
+                segment[1].x,
+                segment[1].y,
+                2.,
+                PREDICTION_COLOR,
+            );
+        }
+    }
+    if let Some(end) = points.last() {
+        draw_circle(end.x, end.y, 4., PREDICTION_COLOR);
+    }
+}
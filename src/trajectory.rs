@@ -0,0 +1,165 @@
+use std::{fmt::Write as _, fs, path::Path};
+
+use macroquad::prelude::*;
+
+use crate::{body_set::BodySet, rigid_body::Forces};
+
+// Start of every binary file, the number is the version of the format
+const BINARY_MAGIC: &[u8; 4] = b"TRJ1";
+
+// One value per column. Forces a body doesn't have are left empty in CSV files and NaN in
+// binary files
+const COLUMNS: [&str; 20] = [
+    "step",
+    "time",
+    "body",
+    "generation",
+    "pos_x",
+    "pos_y",
+    "vel_x",
+    "vel_y",
+    "kinetic_energy",
+    "f_res_x",
+    "f_res_y",
+    "f_g",
+    "f_air_x",
+    "f_air_y",
+    "f_spring",
+    "f_buoyancy",
+    "f_field_x",
+    "f_field_y",
+    "terminal_vel",
+    "submerged_fraction",
+];
+
+// State of one body after a step
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    step: u32,
+    time: f32,
+    body: u32,
+    generation: u32,
+    pos: Vec2,
+    vel: Vec2,
+    kinetic_energy: f32,
+    forces: Forces,
+}
+impl Sample {
+    // Every column after the first four, in the same order
+    fn get_values(&self) -> [Option<f32>; 16] {
+        let f = &self.forces;
+        [
+            Some(self.pos.x),
+            Some(self.pos.y),
+            Some(self.vel.x),
+            Some(self.vel.y),
+            Some(self.kinetic_energy),
+            Some(f.f_res.x),
+            Some(f.f_res.y),
+            f.f_g,
+            f.f_air.map(|f_air| f_air.x),
+            f.f_air.map(|f_air| f_air.y),
+            f.f_spring,
+            f.f_buoyancy,
+            f.f_field.map(|f_field| f_field.x),
+            f.f_field.map(|f_field| f_field.y),
+            f.terminal_vel,
+            f.submerged_fraction,
+        ]
+    }
+}
+
+// Keeps the state of every body every few steps, to export it for analysis
+#[derive(Debug, Clone)]
+pub struct TrajectoryRecorder {
+    // Only every this many steps is kept
+    sample_interval: u32,
+    step: u32,
+    // Time simulated since the recording started
+    time: f32,
+    samples: Vec<Sample>,
+}
+impl TrajectoryRecorder {
+    pub fn new(sample_interval: u32) -> Self {
+        Self {
+            sample_interval: sample_interval.max(1),
+            step: 0,
+            time: 0.,
+            samples: Vec::new(),
+        }
+    }
+
+    // Has to be called right after every step
+    pub fn record_step(&mut self, delta_time: f32, rigid_bodies: &BodySet) {
+        self.step += 1;
+        self.time += delta_time;
+        if !self.step.is_multiple_of(self.sample_interval) {
+            return;
+        }
+        for (handle, rb) in rigid_bodies.iter_with_handles() {
+            let vel = rb.get_vel();
+            self.samples.push(Sample {
+                step: self.step,
+                time: self.time,
+                body: handle.index() as u32,
+                generation: handle.generation(),
+                pos: rb.get_pos(),
+                vel,
+                kinetic_energy: 0.5 * rb.get_mass() * vel.length_squared(),
+                forces: rb.get_forces(),
+            });
+        }
+    }
+
+    pub fn get_sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    // One row per body and sampled step, with a header of the column names
+    pub fn to_csv(&self) -> String {
+        let mut csv = COLUMNS.join(",");
+        csv.push('\n');
+        for sample in &self.samples {
+            let _ = write!(
+                csv,
+                "{},{},{},{}",
+                sample.step, sample.time, sample.body, sample.generation
+            );
+            for value in sample.get_values() {
+                csv.push(',');
+                if let Some(value) = value {
+                    let _ = write!(csv, "{value}");
+                }
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    // The magic bytes and the number of rows as a u32, then the rows. Each one is 80 bytes:
+    // step, body and generation as u32 and the other columns as f32, all little endian and in
+    // the order of the CSV columns
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.samples.len() * COLUMNS.len() * 4);
+        bytes.extend_from_slice(BINARY_MAGIC);
+        bytes.extend_from_slice(&(self.samples.len() as u32).to_le_bytes());
+        for sample in &self.samples {
+            bytes.extend_from_slice(&sample.step.to_le_bytes());
+            bytes.extend_from_slice(&sample.time.to_le_bytes());
+            bytes.extend_from_slice(&sample.body.to_le_bytes());
+            bytes.extend_from_slice(&sample.generation.to_le_bytes());
+            for value in sample.get_values() {
+                bytes.extend_from_slice(&value.unwrap_or(f32::NAN).to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn save_csv(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_csv()).map_err(|err| format!("{}: {err}", path.display()))
+    }
+
+    pub fn save_binary(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_binary()).map_err(|err| format!("{}: {err}", path.display()))
+    }
+}
@@ -0,0 +1,298 @@
+use macroquad::prelude::*;
+
+use crate::{
+    body_set::BodySet,
+    collision::get_time_of_impact,
+    contact_solver::ContactSolver,
+    engine::Variables,
+    island::find_islands,
+    mouse_joint::MouseJoint,
+    rigid_body::{get_combined_restitution, RigidBodyType},
+    sph::SphFluid,
+};
+
+// Bodies slower than this for TIME_TO_SLEEP seconds are put to sleep
+const SLEEP_VEL: f32 = 0.3;
+const TIME_TO_SLEEP: f32 = 1.;
+
+// Everything a step changes or depends on, without any of the ui around it. The engine runs
+// the simulation on one of these, and paths are predicted on a copy
+#[derive(Clone)]
+pub struct World {
+    pub rigid_bodies: BodySet,
+    pub world_size: Vec2,
+    pub vars: Variables,
+    pub sph: Option<SphFluid>,
+    pub solver: ContactSolver,
+    pub speed_limit: Option<f32>,
+    pub sleeping_enabled: bool,
+    pub mouse_joint: Option<MouseJoint>,
+}
+impl World {
+    // A copy without the fluid, its particles would make simulating ahead too slow
+    pub fn without_fluid(&self) -> Self {
+        Self {
+            rigid_bodies: self.rigid_bodies.clone(),
+            world_size: self.world_size,
+            vars: self.vars.clone(),
+            sph: None,
+            solver: self.solver.clone(),
+            speed_limit: self.speed_limit,
+            sleeping_enabled: self.sleeping_enabled,
+            mouse_joint: self.mouse_joint,
+        }
+    }
+
+    // Returns the indices of every two bodies that are touching, and where momentum wasn't conserved
+    pub fn step(&mut self, delta_time: f32) -> (Vec<(usize, usize)>, StepReport) {
+        let start_positions: Vec<Vec2> = (0..self.rigid_bodies.slot_count())
+            .map(|i| {
+                self.rigid_bodies
+                    .get_at(i)
+                    .map_or(Vec2::ZERO, |rb| rb.get_pos())
+            })
+            .collect();
+
+        if let Some(joint) = self.mouse_joint {
+            match self.rigid_bodies.get_mut(joint.body) {
+                Some(rb) => joint.apply(rb.as_mut(), delta_time),
+                // The body was removed while it was being dragged
+                None => self.mouse_joint = None,
+            }
+        }
+
+        //apply forces on the rigidbodies
+        self.rigid_bodies.iter_mut().for_each(|rb| {
+            if rb.get_enabled() && !rb.get_sleeping() {
+                rb.apply_forces(&self.vars, delta_time, self.world_size);
+            }
+        });
+
+        if let Some(sph) = &mut self.sph {
+            sph.update(
+                &self.vars,
+                delta_time,
+                self.world_size,
+                &mut self.rigid_bodies,
+            );
+        }
+
+        // Contacts between bodies can't change their total momentum, only the edges of the world can
+        let momentum_before = get_total_momentum(&self.rigid_bodies);
+        let contact_pairs = self.solver.solve(&mut self.rigid_bodies, self.world_size);
+        self.resolve_fast_bodies(&start_positions);
+        let momentum_change = get_total_momentum(&self.rigid_bodies) - momentum_before;
+
+        let report = StepReport {
+            contact_momentum_error: momentum_change - self.solver.get_boundary_impulse(),
+            ..self.apply_speed_limit()
+        };
+        (contact_pairs, report)
+    }
+
+    // Puts islands of bodies to sleep once all of them have been slow for long enough,
+    // and wakes up every body in an island as soon as one of them starts moving
+    pub fn update_sleeping(&mut self, contact_pairs: &[(usize, usize)], delta_time: f32) {
+        for rb in self.rigid_bodies.iter_mut() {
+            // Springs never touch other bodies, so nothing would wake them up again
+            if !self.sleeping_enabled
+                || rb.get_type() == RigidBodyType::Spring
+                || rb.get_vel().length() > SLEEP_VEL
+            {
+                rb.set_sleep_timer(0.);
+            } else {
+                rb.set_sleep_timer(rb.get_sleep_timer() + delta_time);
+            }
+        }
+
+        // Empty slots end up in islands of their own
+        for island in find_islands(self.rigid_bodies.slot_count(), contact_pairs) {
+            let sleeping = island.iter().all(|&i| {
+                self.rigid_bodies
+                    .get_at(i)
+                    .is_none_or(|rb| !rb.get_enabled() || rb.get_sleep_timer() >= TIME_TO_SLEEP)
+            });
+
+            for i in island {
+                let Some(rb) = self.rigid_bodies.get_at_mut(i) else {
+                    continue;
+                };
+                if sleeping && !rb.get_sleeping() {
+                    rb.set_vel(Vec2::ZERO);
+                }
+                rb.set_sleeping(sleeping);
+            }
+        }
+    }
+
+    // Slows down bodies that are faster than the speed limit, if there is one
+    fn apply_speed_limit(&mut self) -> StepReport {
+        let mut report = StepReport::default();
+        let Some(speed_limit) = self.speed_limit else {
+            return report;
+        };
+        for rb in self.rigid_bodies.iter_mut() {
+            if !rb.get_enabled() || rb.get_vel().length() <= speed_limit {
+                continue;
+            }
+            let vel = rb.get_vel().clamp_length_max(speed_limit);
+            report.speed_limit_momentum += (rb.get_vel() - vel) * rb.get_mass();
+            report.speed_limited_bodies += 1;
+            rb.set_vel(vel);
+        }
+        report
+    }
+
+    // Continuous collision detection for bullets. They get swept from where they started this step
+    // to where they ended up, and stopped at the first body they would have passed through.
+    fn resolve_fast_bodies(&mut self, start_positions: &[Vec2]) {
+        for i in 0..self.rigid_bodies.slot_count() {
+            let Some(rb) = self.rigid_bodies.get_at(i) else {
+                continue;
+            };
+            if !rb.get_bullet() || !rb.get_enabled() || rb.get_sleeping() || rb.get_sensor() {
+                continue;
+            }
+            let Some(shape) = rb.get_shape() else {
+                continue;
+            };
+            let displacement = rb.get_pos() - start_positions[i];
+            let start_shape = shape.translate(-displacement);
+
+            // Find the first body it hits, (index, time of impact, normal)
+            let mut first_impact: Option<(usize, f32, Vec2)> = None;
+            for (j, &other_start) in start_positions.iter().enumerate() {
+                let Some(other) = self.rigid_bodies.get_at(j) else {
+                    continue;
+                };
+                if j == i
+                    || !other.get_enabled()
+                    || other.get_sensor()
+                    || !self.solver.should_collide(&self.rigid_bodies, i, j)
+                {
+                    continue;
+                }
+                let Some(other_shape) = other.get_shape() else {
+                    continue;
+                };
+                // Sweep with the relative motion, as if the other body stood still
+                let other_displacement = other.get_pos() - other_start;
+                let other_start_shape = other_shape.translate(-other_displacement);
+                let Some((t, normal)) = get_time_of_impact(
+                    &start_shape,
+                    displacement - other_displacement,
+                    &other_start_shape,
+                ) else {
+                    continue;
+                };
+                if first_impact.is_none_or(|(_, first_t, _)| t < first_t) {
+                    first_impact = Some((j, t, normal));
+                }
+            }
+
+            if let Some((j, t, normal)) = first_impact {
+                self.rigid_bodies[i].set_pos(start_positions[i] + displacement * t);
+                self.apply_collision_impulse(j, i, normal);
+            }
+        }
+    }
+
+    // Applies the impulse between body `j` and `i`, `normal` points from `j` towards `i`
+    fn apply_collision_impulse(&mut self, j: usize, i: usize, normal: Vec2) {
+        let rb0 = &self.rigid_bodies[j];
+        let rb1 = &self.rigid_bodies[i];
+
+        // Calculate relative velocity
+        let relative_vel = rb1.get_vel() - rb0.get_vel();
+
+        // Calculate relative velocity in terms of the normal direction
+        let vel_along_normal = normal.dot(relative_vel);
+        if vel_along_normal > 0. {
+            return; // Only resolve collision if objects are moving towards each other
+        }
+        // v1* = v1 - J * n / m1
+        // v1* / v1 = -j * n / m1
+        //-j =  v1* / v1 / (n / m1)
+        let e = get_combined_restitution(rb0.as_ref(), rb1.as_ref());
+
+        let inverse_mass_0 = 1. / rb0.get_mass();
+        let inverse_mass_1 = 1. / rb1.get_mass();
+
+        // Calculate impulse scalar
+        let mut impulse_scalar = -(1. + e) * vel_along_normal;
+        impulse_scalar /= inverse_mass_0 + inverse_mass_1;
+
+        // Calculate impulse
+        let impulse = impulse_scalar * normal;
+
+        // Calculate new velocity based on impulse
+        let new_vel_0 = rb0.get_vel() - inverse_mass_0 * impulse;
+        let new_vel_1 = rb1.get_vel() + inverse_mass_1 * impulse;
+
+        // Set new velocities
+        self.rigid_bodies[j].set_vel(new_vel_0);
+        self.rigid_bodies[i].set_vel(new_vel_1);
+    }
+}
+
+// Where momentum wasn't conserved during the last step
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StepReport {
+    // Change in total momentum from contacts that the edges of the world don't account for
+    pub contact_momentum_error: Vec2,
+    pub speed_limit_momentum: Vec2,
+    pub speed_limited_bodies: usize,
+}
+
+fn get_total_momentum(rigid_bodies: &BodySet) -> Vec2 {
+    rigid_bodies
+        .iter()
+        .filter(|rb| rb.get_enabled())
+        .fold(Vec2::ZERO, |sum, rb| sum + rb.get_vel() * rb.get_mass())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenes::Scene;
+
+    // A thin wall with a bullet and a regular ball flying at it, see the scene description
+    fn get_bullet_world() -> World {
+        let scene = Scene::from_ron("bullet", include_str!("../scenes/bullet.ron")).unwrap();
+        let mut rigid_bodies = BodySet::new();
+        for rb in scene.get_rigid_bodies() {
+            rigid_bodies.insert(rb);
+        }
+        World {
+            rigid_bodies,
+            world_size: scene.get_world_size(),
+            vars: scene.get_variables(),
+            sph: None,
+            solver: ContactSolver::new(),
+            speed_limit: None,
+            sleeping_enabled: true,
+            mouse_joint: None,
+        }
+    }
+
+    #[test]
+    fn bullets_dont_go_through_thin_walls() {
+        let mut world = get_bullet_world();
+        let wall_left = world.rigid_bodies[0].get_pos().x;
+        // Long enough for both balls to get past the wall, but not to come back from the far side
+        for _ in 0..8 {
+            let (contact_pairs, _) = world.step(1. / 60.);
+            world.update_sleeping(&contact_pairs, 1. / 60.);
+        }
+        let bullet = &world.rigid_bodies[1];
+        let ball = &world.rigid_bodies[2];
+        assert!(
+            bullet.get_pos().x < wall_left,
+            "bullet at {}",
+            bullet.get_pos()
+        );
+        assert!(bullet.get_vel().x < 0.);
+        // Without continuous collision detection the ball tunnels through
+        assert!(ball.get_pos().x > wall_left, "ball at {}", ball.get_pos());
+    }
+}